@@ -1,17 +1,82 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec;
 use alloc::vec::Vec;
+use core::mem;
+use core::ops::Range;
 use core::str;
 use core::str::FromStr;
 
 use serde::de;
-use serde::de::{DeserializeSeed, Unexpected, Visitor};
+use serde::de::{DeserializeSeed, Expected, Unexpected, Visitor};
 
+use crate::encoding::{BytesEncoding, Encoding};
 use crate::error;
-use crate::error::Result;
-use crate::read::{Read, SliceRead, StrRead};
+use crate::error::{Error, ErrorCode, Result};
+#[cfg(feature = "std")]
+use crate::read::IoRead;
+use crate::read::{Position, Read, Reference, SliceRead, StrRead};
+
+/// The flavor of `.properties` syntax understood by a [`Deserializer`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Dialect {
+    /// Literal `key=value` lines as found in Android `build.prop` and Magisk
+    /// `module.prop` files. Backslashes are kept as written, except that a
+    /// line ending in an odd number of them continues on the next line, so
+    /// `dir=C:\` swallows the line that follows.
+    Lenient,
+    /// The syntax accepted by `java.util.Properties.load`, including escape
+    /// sequences, `\uXXXX` code units, line continuations and whitespace as a
+    /// key-value separator. This is what the [`Serializer`] writes, and the
+    /// default so that [`from_str`] reads it back.
+    ///
+    /// [`Serializer`]: crate::Serializer
+    #[default]
+    Java,
+}
+
+/// What a [`Deserializer`] does with a key that is set more than once.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicateKey {
+    /// Passes every occurrence on to the type being deserialized. Derived
+    /// structs reject a duplicate field and maps keep the last value.
+    #[default]
+    Visit,
+    /// Fails with an error that names the lines of both occurrences.
+    Error,
+    First,
+    Last,
+    /// Gives a sequence such as a `Vec<T>` every value in order, each split
+    /// on the sequence separator, and any other type the last one.
+    Collect,
+}
 
 pub struct Deserializer<R> {
     read: R,
     inner: Vec<u8>,
+    borrowed: Option<(usize, usize)>,
+    key: Vec<u8>,
+    key_position: Position,
+    key_offset: usize,
+    value_offset: usize,
+    dialect: Dialect,
+    encoding: Encoding,
+    infer_types: bool,
+    null_token: Option<String>,
+    empty_strings: bool,
+    lenient_literals: bool,
+    duplicate_key: DuplicateKey,
+    // Every occurrence of the key whose value is being deserialized, under
+    // `DuplicateKey::Collect`.
+    collected: Option<Vec<Entry>>,
+    dotted_keys: bool,
+    seq_separator: char,
+    bytes_encoding: BytesEncoding,
+    // The keys nested under the one whose value is being deserialized.
+    children: Option<Vec<Node>>,
+    // Set once the top-level map is being visited. From then on every key
+    // and value is a scalar.
+    in_map: bool,
 }
 
 impl<'de, R: Read<'de>> Deserializer<R> {
@@ -19,8 +84,94 @@ impl<'de, R: Read<'de>> Deserializer<R> {
         Deserializer {
             read,
             inner: Vec::new(),
+            borrowed: None,
+            key: Vec::new(),
+            key_position: Position { line: 0, column: 0 },
+            key_offset: 0,
+            value_offset: 0,
+            dialect: Dialect::default(),
+            encoding: Encoding::default(),
+            infer_types: false,
+            null_token: None,
+            empty_strings: false,
+            lenient_literals: false,
+            duplicate_key: DuplicateKey::default(),
+            collected: None,
+            dotted_keys: false,
+            seq_separator: ',',
+            bytes_encoding: BytesEncoding::Hex,
+            children: None,
+            in_map: false,
         }
     }
+
+    pub fn with_dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    pub fn with_duplicate_key(mut self, duplicate_key: DuplicateKey) -> Self {
+        self.duplicate_key = duplicate_key;
+        self
+    }
+
+    /// Treats the dots in keys as nesting, so that `server.port` sets the
+    /// `port` field of a `server` struct or map. Each key then gets a single
+    /// value, the last one under `DuplicateKey::Visit`.
+    pub fn with_dotted_keys(mut self, dotted_keys: bool) -> Self {
+        self.dotted_keys = dotted_keys;
+        self
+    }
+
+    /// Sets what separates the elements of a sequence written as a single
+    /// value, `,` by default. Spaces around each element are dropped.
+    ///
+    /// With dotted keys, sequences can also be written one element per key,
+    /// as `hosts.0` or `hosts[0]`.
+    pub fn with_seq_separator(mut self, separator: char) -> Self {
+        self.seq_separator = separator;
+        self
+    }
+
+    /// Sets how byte strings are encoded, hex by default.
+    pub fn with_bytes_encoding(mut self, encoding: BytesEncoding) -> Self {
+        self.bytes_encoding = encoding;
+        self
+    }
+
+    /// Makes `deserialize_any` visit values that look like booleans, integers
+    /// or floats as such, instead of as strings. This is what untagged enums,
+    /// flattened fields and dynamic values like `serde_json::Value` see.
+    pub fn with_type_inference(mut self, infer_types: bool) -> Self {
+        self.infer_types = infer_types;
+        self
+    }
+
+    /// Reads values equal to `token`, such as `null`, as `None` instead of
+    /// empty ones. Empty values then read as empty strings.
+    pub fn with_null_token<S: Into<String>>(mut self, token: S) -> Self {
+        self.null_token = Some(token.into());
+        self
+    }
+
+    /// Reads booleans written as `1`/`0`, `yes`/`no` or `on`/`off` in any
+    /// case, and integers written with `0x`, `0o` or `0b` prefixes or with
+    /// `_` between digits.
+    pub fn with_lenient_literals(mut self, lenient: bool) -> Self {
+        self.lenient_literals = lenient;
+        self
+    }
+
+    /// Reads empty values as `Some("")` rather than `None`.
+    pub fn with_empty_strings(mut self, empty_strings: bool) -> Self {
+        self.empty_strings = empty_strings;
+        self
+    }
 }
 
 impl<'a> Deserializer<SliceRead<'a>> {
@@ -35,8 +186,15 @@ impl<'a> Deserializer<StrRead<'a>> {
     }
 }
 
+#[cfg(feature = "std")]
+impl<R: std::io::Read> Deserializer<IoRead<R>> {
+    pub fn from_reader(reader: R) -> Self {
+        Deserializer::new(IoRead::new(reader))
+    }
+}
+
 impl<'de, R: Read<'de>> Deserializer<R> {
-    fn next_char(&mut self) -> Option<u8> {
+    fn next_char(&mut self) -> Result<Option<u8>> {
         self.read.next()
     }
 
@@ -44,63 +202,485 @@ impl<'de, R: Read<'de>> Deserializer<R> {
         self.read.discard()
     }
 
-    fn peek(&mut self) -> Option<u8> {
+    fn peek(&mut self) -> Result<Option<u8>> {
         self.read.peek()
     }
 
-    fn end(&mut self) -> Result<()> {
-        match self.peek() {
-            Some(_) => Err(de::Error::custom("not over yet")),
+    pub fn end(&mut self) -> Result<()> {
+        match self.peek()? {
+            Some(_) => Err(self.peek_error(ErrorCode::TrailingCharacters)),
             None => Ok(()),
         }
     }
 
-    fn parse_whitespace(&mut self) -> Option<u8> {
+    fn error(&self, reason: ErrorCode) -> Error {
+        let position = self.read.position();
+        Error::syntax(
+            reason,
+            position.line,
+            position.column,
+            self.read.byte_offset(),
+        )
+    }
+
+    fn peek_error(&self, reason: ErrorCode) -> Error {
+        let position = self.read.peek_position();
+        Error::syntax(
+            reason,
+            position.line,
+            position.column,
+            self.read.byte_offset(),
+        )
+    }
+
+    // Points errors raised while deserializing the current entry at its key.
+    fn fix_position(&self, err: Error) -> Error {
+        err.fix_position(
+            self.key_position.line,
+            self.key_position.column,
+            self.key_offset,
+        )
+    }
+
+    fn invalid_value(&self, exp: &dyn Expected) -> Error {
+        de::Error::invalid_value(Unexpected::Str(self.parse_str().unwrap_or("")), exp)
+    }
+
+    // Skips blank and comment lines, returning `false` at the end of input.
+    fn parse_whitespace(&mut self) -> Result<bool> {
+        loop {
+            match self.peek()? {
+                None => return Ok(false),
+                Some(b' ' | b'\t' | b'\x0c' | b'\r' | b'\n') => self.eat_char(),
+                Some(b'#' | b'!') => self.skip_line()?,
+                Some(_) => return Ok(true),
+            }
+        }
+    }
+
+    // Returns the offset just past the value, see below.
+    fn parse_value(&mut self) -> Result<usize> {
+        let mut scratch = mem::take(&mut self.inner);
+        scratch.clear();
+        self.value_offset = self.read.byte_offset();
+        let end = match self.dialect {
+            Dialect::Lenient => self.parse_lenient_value(&mut scratch)?,
+            Dialect::Java => self.parse_java_value(&mut scratch)?,
+        };
+        self.set_inner(scratch, end)?;
+        Ok(end)
+    }
+
+    fn parse_key(&mut self) -> Result<bool> {
+        if !self.parse_whitespace()? {
+            return Ok(false);
+        }
+        self.parse_key_bytes()?;
+        Ok(true)
+    }
+
+    fn parse_key_bytes(&mut self) -> Result<usize> {
+        self.key_position = self.read.peek_position();
+        self.key_offset = self.read.byte_offset();
+
+        let mut scratch = mem::take(&mut self.inner);
+        scratch.clear();
+        let end = match self.dialect {
+            Dialect::Lenient => self.parse_lenient_key(&mut scratch)?,
+            Dialect::Java => self.parse_java_key(&mut scratch)?,
+        };
+        self.set_inner(scratch, end)?;
+        self.key.clone_from(&self.inner);
+        Ok(end)
+    }
+
+    // Parses one physical line, or one logical line if it is an entry, for a
+    // `Document`. Unlike `parse_whitespace`, blank and comment lines are
+    // reported one by one, and so are lenient lines without a separator, such
+    // as the `import` lines of a `build.prop`.
+    pub(crate) fn parse_line(&mut self) -> Result<Option<Line>> {
+        let start = self.read.byte_offset();
         loop {
-            match self.next_char()? {
-                b' ' | b'\n' | b'\t' | b'\r' => {}
-                other => return Some(other),
+            match self.peek()? {
+                Some(b' ' | b'\t' | b'\x0c') => self.eat_char(),
+                None if self.read.byte_offset() == start => return Ok(None),
+                None | Some(b'\r' | b'\n') => {
+                    self.skip_eol()?;
+                    return Ok(Some(Line::Trivia));
+                }
+                Some(b'#' | b'!') => {
+                    self.skip_line()?;
+                    self.skip_eol()?;
+                    return Ok(Some(Line::Trivia));
+                }
+                Some(_) => break,
             }
         }
+
+        let key_end = match self.parse_key_bytes() {
+            Ok(key_end) => key_end,
+            Err(err) if self.dialect == Dialect::Lenient && err.is_missing_separator() => {
+                self.skip_eol()?;
+                return Ok(Some(Line::Trivia));
+            }
+            Err(err) => return Err(err),
+        };
+        let key = self.key_offset..key_end;
+        let value_end = self.parse_value()?;
+        let value = self.value_offset..value_end;
+        self.skip_eol()?;
+        Ok(Some(Line::Entry { key, value }))
     }
 
-    fn parse_value(&mut self) -> Vec<u8> {
-        if let Some(b' ') = self.peek() {
+    // Reads the rest of the input, grouping the entries by key in order of
+    // first appearance.
+    fn parse_entries(&mut self) -> Result<Vec<Node>> {
+        let mut root = Node::new(Vec::new(), None, self.key_position, 0);
+        let mut lines = BTreeMap::new();
+        while self.parse_key()? {
+            let (key, borrowed) = (self.inner.clone(), self.borrowed);
+            let (position, offset) = (self.key_position, self.key_offset);
+            if self.duplicate_key == DuplicateKey::Error {
+                if let Some(&line) = lines.get(&key) {
+                    let code = ErrorCode::DuplicateKey(line);
+                    let err = Error::syntax(code, position.line, position.column, offset);
+                    return Err(err.prepend_key(self.key_str()));
+                }
+                lines.insert(key.clone(), position.line);
+            }
+
+            self.parse_value()?;
+            let entry = Entry {
+                value: (self.inner.clone(), self.borrowed),
+                position,
+            };
+
+            let mut node = &mut root;
+            if self.dotted_keys {
+                for Range { start, end } in key_segments(&key) {
+                    let range = borrowed.map(|(offset, _)| (offset + start, offset + end));
+                    node = node.child(&key[start..end], range, position, offset);
+                }
+            } else {
+                node = node.child(&key, borrowed, position, offset);
+            }
+            node.entries.push(entry);
+        }
+        Ok(root.children)
+    }
+
+    fn load_key(&mut self, node: &Node) {
+        self.key.clone_from(&node.key.0);
+        self.inner.clone_from(&node.key.0);
+        self.borrowed = node.key.1;
+        self.key_position = node.position;
+        self.key_offset = node.offset;
+    }
+
+    fn load_value(&mut self, entry: Entry) {
+        (self.inner, self.borrowed) = entry.value;
+        self.key_position = entry.position;
+    }
+
+    // Deserializes the value of a buffered key, along with the keys nested
+    // under it.
+    fn deserialize_node<T>(
+        &mut self,
+        key: &[u8],
+        mut entries: Vec<Entry>,
+        children: Vec<Node>,
+        seed: T,
+    ) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if self.duplicate_key == DuplicateKey::First {
+            entries.truncate(1);
+        }
+        match entries.last() {
+            Some(entry) if self.duplicate_key == DuplicateKey::Collect => {
+                self.load_value(entry.clone());
+                self.collected = Some(entries);
+            }
+            Some(_) => self.load_value(entries.pop().unwrap()),
+            // A key that only has keys nested under it.
+            None => {
+                self.inner.clear();
+                self.borrowed = None;
+            }
+        }
+        self.children = (!children.is_empty()).then_some(children);
+
+        // The key in `self` may have been replaced by a nested one by now.
+        let value = seed.deserialize(&mut *self).map_err(|err| {
+            let key = str::from_utf8(key).unwrap_or_default();
+            self.fix_position(err.prepend_key(key))
+        });
+        self.collected = None;
+        self.children = None;
+        value
+    }
+
+    // Splits the current value on the sequence separator.
+    fn split_value(&self) -> Vec<Entry> {
+        let mut entries = Vec::new();
+        if self.inner.is_empty() {
+            return entries;
+        }
+
+        let mut buf = [0; 4];
+        let separator = self.seq_separator.encode_utf8(&mut buf).as_bytes();
+        let mut start = 0;
+        loop {
+            let end = self.inner[start..]
+                .windows(separator.len())
+                .position(|window| window == separator)
+                .map_or(self.inner.len(), |i| start + i);
+
+            let element = &self.inner[start..end];
+            let leading = element.iter().take_while(|&&b| b == b' ').count();
+            let trailing = element[leading..]
+                .iter()
+                .rev()
+                .take_while(|&&b| b == b' ')
+                .count();
+            let (first, last) = (start + leading, end - trailing);
+            entries.push(Entry {
+                value: (
+                    self.inner[first..last].to_vec(),
+                    self.borrowed
+                        .map(|(offset, _)| (offset + first, offset + last)),
+                ),
+                position: self.key_position,
+            });
+
+            if end == self.inner.len() {
+                return entries;
+            }
+            start = end + separator.len();
+        }
+    }
+
+    pub(crate) fn key_str(&self) -> &str {
+        str::from_utf8(&self.key).unwrap_or_default()
+    }
+
+    pub(crate) fn value_str(&self) -> &str {
+        str::from_utf8(&self.inner).unwrap_or_default()
+    }
+
+    pub(crate) fn byte_offset(&self) -> usize {
+        self.read.byte_offset()
+    }
+
+    // The `parse_*_key` and `parse_*_value` functions return the offset just
+    // past the last byte of input that makes up the key or value.
+
+    fn parse_lenient_value(&mut self, slice: &mut Vec<u8>) -> Result<usize> {
+        if let Some(b' ') = self.peek()? {
             self.eat_char()
         }
+        self.value_offset = self.read.byte_offset();
 
-        let mut slice = Vec::new();
         loop {
-            match self.next_char() {
-                Some(b'\n' | b'\t' | b'\r') | None => {
-                    return slice;
+            match self.peek()? {
+                Some(eol @ (b'\n' | b'\r')) => {
+                    // A logical line goes on if it ends in an odd number of
+                    // backslashes, the last of which is dropped.
+                    let backslashes = slice.iter().rev().take_while(|&&b| b == b'\\').count();
+                    if backslashes % 2 == 0 {
+                        return Ok(self.read.byte_offset());
+                    }
+                    self.eat_char();
+                    slice.pop();
+                    self.skip_continuation(eol)?;
+                }
+                None => return Ok(self.read.byte_offset()),
+                Some(b) => {
+                    self.eat_char();
+                    self.encoding.push_byte(slice, b);
                 }
-                Some(b) => slice.push(b),
             };
         }
     }
 
-    fn parse_comment(&mut self) -> Option<u8> {
+    fn parse_lenient_key(&mut self, slice: &mut Vec<u8>) -> Result<usize> {
         loop {
-            match self.parse_whitespace()? {
-                b'#' | b'!' => {
-                    self.parse_value();
+            match self.peek()? {
+                Some(b'=' | b':') => {
+                    let trailing = slice.iter().rev().take_while(|&&b| is_space(b)).count();
+                    let end = self.read.byte_offset() - trailing;
+                    self.eat_char();
+                    slice.truncate(slice.len() - trailing);
+                    return Ok(end);
+                }
+                Some(b'\r' | b'\n') => return Err(self.error(ErrorCode::ExpectedSeparator)),
+                None => return Err(self.error(ErrorCode::EofWhileParsingKey)),
+                Some(b) => {
+                    self.eat_char();
+                    self.encoding.push_byte(slice, b);
                 }
-                b => return Some(b),
             };
         }
     }
 
-    fn parse_key(&mut self) -> Option<Vec<u8>> {
-        let mut slice = Vec::new();
+    fn skip_line(&mut self) -> Result<()> {
+        while let Some(b) = self.peek()? {
+            if let b'\r' | b'\n' = b {
+                break;
+            }
+            self.eat_char();
+        }
+        Ok(())
+    }
+
+    fn skip_eol(&mut self) -> Result<()> {
+        if self.peek()? == Some(b'\r') {
+            self.eat_char();
+        }
+        if self.peek()? == Some(b'\n') {
+            self.eat_char();
+        }
+        Ok(())
+    }
+
+    fn skip_continuation(&mut self, eol: u8) -> Result<()> {
+        if eol == b'\r' && self.peek()? == Some(b'\n') {
+            self.eat_char();
+        }
+        while let Some(b' ' | b'\t' | b'\x0c') = self.peek()? {
+            self.eat_char();
+        }
+        Ok(())
+    }
+
+    // The separator is left in place so that `parse_java_value` can tell a
+    // `key = value` line apart from a `key =value` or a `key value` one.
+    fn parse_java_key(&mut self, slice: &mut Vec<u8>) -> Result<usize> {
+        loop {
+            match self.peek()? {
+                None | Some(b'=' | b':' | b' ' | b'\t' | b'\x0c' | b'\r' | b'\n') => {
+                    return Ok(self.read.byte_offset());
+                }
+                Some(b'\\') => {
+                    self.eat_char();
+                    self.parse_java_escape(slice)?;
+                }
+                Some(b) => {
+                    self.eat_char();
+                    self.encoding.push_byte(slice, b);
+                }
+            }
+        }
+    }
+
+    fn parse_java_value(&mut self, slice: &mut Vec<u8>) -> Result<usize> {
+        let mut separator = false;
+        let mut started = false;
         loop {
-            match self.parse_comment()? {
-                b'=' | b':' => {
-                    return Some(slice);
+            match self.peek()? {
+                None | Some(b'\r' | b'\n') => {
+                    if !started {
+                        self.value_offset = self.read.byte_offset();
+                    }
+                    return Ok(self.read.byte_offset());
+                }
+                Some(b' ' | b'\t' | b'\x0c') if !started => self.eat_char(),
+                Some(b'=' | b':') if !started && !separator => {
+                    self.eat_char();
+                    separator = true;
                 }
-                b => slice.push(b),
+                Some(b) => {
+                    if !started {
+                        self.value_offset = self.read.byte_offset();
+                    }
+                    self.eat_char();
+                    if b == b'\\' {
+                        started |= self.parse_java_escape(slice)?;
+                    } else {
+                        started = true;
+                        self.encoding.push_byte(slice, b);
+                    }
+                }
+            }
+        }
+    }
+
+    // Returns `false` if the backslash was a line continuation, which doesn't
+    // produce a character.
+    fn parse_java_escape(&mut self, slice: &mut Vec<u8>) -> Result<bool> {
+        let ch = match self.next_char()? {
+            None => return Ok(false),
+            Some(eol @ (b'\r' | b'\n')) => {
+                self.skip_continuation(eol)?;
+                return Ok(false);
+            }
+            Some(b't') => '\t',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b'f') => '\x0c',
+            Some(b'u') => self.parse_unicode_escape()?,
+            Some(b) => {
+                self.encoding.push_byte(slice, b);
+                return Ok(true);
+            }
+        };
+
+        let mut buf = [0; 4];
+        slice.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+        Ok(true)
+    }
+
+    fn parse_unicode_escape(&mut self) -> Result<char> {
+        let n = match self.decode_hex_escape()? {
+            n @ 0xD800..=0xDBFF => {
+                let n2 = match (self.next_char()?, self.next_char()?) {
+                    (Some(b'\\'), Some(b'u')) => self.decode_hex_escape()?,
+                    _ => 0,
+                };
+                if !(0xDC00..=0xDFFF).contains(&n2) {
+                    return Err(self.error(ErrorCode::LoneSurrogate));
+                }
+                (((n - 0xD800) as u32) << 10 | (n2 - 0xDC00) as u32) + 0x1_0000
+            }
+            0xDC00..=0xDFFF => {
+                return Err(self.error(ErrorCode::LoneSurrogate));
+            }
+            n => n as u32,
+        };
+
+        char::from_u32(n).ok_or_else(|| self.error(ErrorCode::LoneSurrogate))
+    }
+
+    fn decode_hex_escape(&mut self) -> Result<u16> {
+        let mut n = 0;
+        for _ in 0..4 {
+            let digit = match self.next_char()? {
+                Some(b @ b'0'..=b'9') => b - b'0',
+                Some(b @ b'a'..=b'f') => b - b'a' + 10,
+                Some(b @ b'A'..=b'F') => b - b'A' + 10,
+                Some(_) => return Err(self.error(ErrorCode::InvalidEscape)),
+                None => return Err(self.error(ErrorCode::EofWhileParsingEscape)),
             };
+            n = (n << 4) + digit as u16;
+        }
+        Ok(n)
+    }
+
+    fn set_inner(&mut self, bytes: Vec<u8>, end: usize) -> Result<()> {
+        if str::from_utf8(&bytes).is_err() {
+            return Err(self.error(ErrorCode::InvalidUtf8));
         }
+
+        // Unescaping and transcoding change the bytes, so if they are still
+        // the same as the input they were parsed from, that input can be
+        // borrowed instead.
+        self.borrowed = end
+            .checked_sub(bytes.len())
+            .filter(|&start| self.read.input_slice(start, end) == Some(&bytes[..]))
+            .map(|start| (start, end));
+        self.inner = bytes;
+        Ok(())
     }
 
     fn parse_str(&self) -> Option<&str> {
@@ -110,33 +690,249 @@ impl<'de, R: Read<'de>> Deserializer<R> {
         }
     }
 
+    fn parse_borrowed_str(&self) -> Option<Reference<'de, '_, str>> {
+        let borrowed = self
+            .borrowed
+            .and_then(|(start, end)| self.read.input_slice(start, end))
+            .and_then(|bytes| str::from_utf8(bytes).ok());
+        match borrowed {
+            Some("") => None,
+            Some(v) => Some(Reference::Borrowed(v)),
+            None => self.parse_str().map(Reference::Copied),
+        }
+    }
+
+    // Whether the value stands for `None`.
+    fn is_null(&self) -> bool {
+        match &self.null_token {
+            Some(token) => self.inner == token.as_bytes(),
+            None => !self.empty_strings && self.inner.is_empty(),
+        }
+    }
+
+    fn parse_scalar<V>(&self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.is_null() {
+            return visitor.visit_unit();
+        }
+        let reference = match self.parse_borrowed_str() {
+            None => return visitor.visit_str(""),
+            Some(reference) => reference,
+        };
+        let v = match reference {
+            Reference::Borrowed(v) => v,
+            Reference::Copied(v) => v,
+        };
+
+        if self.infer_types {
+            if let Ok(b) = v.parse() {
+                return visitor.visit_bool(b);
+            }
+            if let Ok(n) = v.parse() {
+                return visitor.visit_i64(n);
+            }
+            if let Ok(n) = v.parse() {
+                return visitor.visit_u64(n);
+            }
+            // Only plain decimal notation, so that words like `inf` and `NaN`
+            // stay strings.
+            if v.bytes()
+                .all(|b| b.is_ascii_digit() || b"+-.eE".contains(&b))
+            {
+                if let Ok(n) = v.parse() {
+                    return visitor.visit_f64(n);
+                }
+            }
+        }
+
+        match reference {
+            Reference::Borrowed(v) => visitor.visit_borrowed_str(v),
+            Reference::Copied(v) => visitor.visit_str(v),
+        }
+    }
+
     fn parse_any<T: FromStr>(&self) -> Option<T> {
         T::from_str(self.parse_str()?).ok()
     }
+
+    fn parse_bool(&self) -> Option<bool> {
+        if !self.lenient_literals {
+            return self.parse_any();
+        }
+        let v = self.parse_str()?;
+        let is = |words: [&str; 4]| words.iter().any(|w| w.eq_ignore_ascii_case(v));
+        if is(["true", "1", "yes", "on"]) {
+            Some(true)
+        } else if is(["false", "0", "no", "off"]) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    fn parse_signed<T: FromStr + TryFrom<i128>>(&self) -> Option<T> {
+        if !self.lenient_literals {
+            return self.parse_any();
+        }
+        let (negative, n) = parse_lenient_int(self.parse_str()?)?;
+        let n = if negative {
+            0i128.checked_sub_unsigned(n)?
+        } else {
+            i128::try_from(n).ok()?
+        };
+        T::try_from(n).ok()
+    }
+
+    fn parse_unsigned<T: FromStr + TryFrom<u128>>(&self) -> Option<T> {
+        if !self.lenient_literals {
+            return self.parse_any();
+        }
+        match parse_lenient_int(self.parse_str()?)? {
+            (true, n) if n != 0 => None,
+            (_, n) => T::try_from(n).ok(),
+        }
+    }
+}
+
+// Splits an integer into its sign and magnitude, allowing radix prefixes and
+// `_` between digits.
+fn parse_lenient_int(v: &str) -> Option<(bool, u128)> {
+    let (negative, v) = match v.strip_prefix('-') {
+        Some(v) => (true, v),
+        None => (false, v.strip_prefix('+').unwrap_or(v)),
+    };
+    let (radix, digits) = match v.get(..2) {
+        Some("0x" | "0X") => (16, &v[2..]),
+        Some("0o" | "0O") => (8, &v[2..]),
+        Some("0b" | "0B") => (2, &v[2..]),
+        _ => (10, v),
+    };
+
+    let valid = digits
+        .bytes()
+        .all(|b| b == b'_' || (b as char).is_digit(radix));
+    if !valid || digits.starts_with('_') || digits.ends_with('_') {
+        return None;
+    }
+    let digits: String = digits.chars().filter(|&c| c != '_').collect();
+    let n = u128::from_str_radix(&digits, radix).ok()?;
+    Some((negative, n))
 }
 
-impl<'de, 'a, R: Read<'de>> de::Deserializer<'de> for &'a mut Deserializer<R> {
+// A value buffered by `parse_entries`, along with what `borrowed` was for it.
+#[derive(Clone)]
+struct Entry {
+    value: (Vec<u8>, Option<(usize, usize)>),
+    position: Position,
+}
+
+// A key buffered by `parse_entries` and every value set for it. With dotted
+// keys, a node is one segment of a key, and the keys under it are children.
+struct Node {
+    key: (Vec<u8>, Option<(usize, usize)>),
+    position: Position,
+    offset: usize,
+    entries: Vec<Entry>,
+    children: Vec<Node>,
+    indices: BTreeMap<Vec<u8>, usize>,
+}
+
+impl Node {
+    fn new(
+        key: Vec<u8>,
+        borrowed: Option<(usize, usize)>,
+        position: Position,
+        offset: usize,
+    ) -> Self {
+        Node {
+            key: (key, borrowed),
+            position,
+            offset,
+            entries: Vec::new(),
+            children: Vec::new(),
+            indices: BTreeMap::new(),
+        }
+    }
+
+    fn child(
+        &mut self,
+        key: &[u8],
+        borrowed: Option<(usize, usize)>,
+        position: Position,
+        offset: usize,
+    ) -> &mut Node {
+        let index = match self.indices.get(key) {
+            Some(&index) => index,
+            None => {
+                let index = self.children.len();
+                self.indices.insert(key.to_vec(), index);
+                self.children
+                    .push(Node::new(key.to_vec(), borrowed, position, offset));
+                index
+            }
+        };
+        &mut self.children[index]
+    }
+}
+
+pub(crate) enum Line {
+    /// A blank or comment line, or a lenient line without a separator.
+    Trivia,
+    /// The byte ranges of an entry's key and value.
+    Entry {
+        key: Range<usize>,
+        value: Range<usize>,
+    },
+}
+
+// Splits a dotted key into its segments, reading `hosts[0]` as `hosts.0`.
+fn key_segments(key: &[u8]) -> Vec<Range<usize>> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    for (i, &b) in key.iter().enumerate() {
+        match b {
+            // A `.` or `[` right after a `]` doesn't end another segment.
+            b'.' | b'[' if i == start && i > 0 && key[i - 1] == b']' => start = i + 1,
+            b'.' | b'[' | b']' => {
+                segments.push(start..i);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if start < key.len() || key.last() != Some(&b']') {
+        segments.push(start..key.len());
+    }
+    segments
+}
+
+fn is_space(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\x0c')
+}
+
+impl<'de, R: Read<'de>> de::Deserializer<'de> for &mut Deserializer<R> {
     type Error = error::Error;
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_unit()
+        if self.in_map && self.children.is_none() {
+            self.parse_scalar(visitor)
+        } else {
+            self.deserialize_map(visitor)
+        }
     }
 
     fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        match self.parse_any() {
+        match self.parse_bool() {
             Some(v) => visitor.visit_bool(v),
-            None => {
-                return Err(de::Error::invalid_value(
-                    Unexpected::Str(self.parse_str().unwrap_or("")),
-                    &"boolean",
-                ))
-            }
+            None => Err(self.invalid_value(&"boolean")),
         }
     }
 
@@ -165,14 +961,19 @@ impl<'de, 'a, R: Read<'de>> de::Deserializer<'de> for &'a mut Deserializer<R> {
     where
         V: Visitor<'de>,
     {
-        match self.parse_any() {
+        match self.parse_signed() {
             Some(v) => visitor.visit_i64(v),
-            None => {
-                return Err(de::Error::invalid_value(
-                    Unexpected::Str(self.parse_str().unwrap_or("")),
-                    &"signed integer",
-                ))
-            }
+            None => Err(self.invalid_value(&"signed integer")),
+        }
+    }
+
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.parse_signed() {
+            Some(v) => visitor.visit_i128(v),
+            None => Err(self.invalid_value(&"signed integer")),
         }
     }
 
@@ -201,14 +1002,19 @@ impl<'de, 'a, R: Read<'de>> de::Deserializer<'de> for &'a mut Deserializer<R> {
     where
         V: Visitor<'de>,
     {
-        match self.parse_any() {
+        match self.parse_unsigned() {
             Some(v) => visitor.visit_u64(v),
-            None => {
-                return Err(de::Error::invalid_value(
-                    Unexpected::Str(self.parse_str().unwrap_or("")),
-                    &"unsigned integer",
-                ))
-            }
+            None => Err(self.invalid_value(&"unsigned integer")),
+        }
+    }
+
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.parse_unsigned() {
+            Some(v) => visitor.visit_u128(v),
+            None => Err(self.invalid_value(&"unsigned integer")),
         }
     }
 
@@ -225,29 +1031,28 @@ impl<'de, 'a, R: Read<'de>> de::Deserializer<'de> for &'a mut Deserializer<R> {
     {
         match self.parse_any() {
             Some(v) => visitor.visit_f64(v),
-            None => {
-                return Err(de::Error::invalid_value(
-                    Unexpected::Str(self.parse_str().unwrap_or("")),
-                    &"float",
-                ))
-            }
+            None => Err(self.invalid_value(&"float")),
         }
     }
 
-    fn deserialize_char<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Err(de::Error::custom("unsupported char"))
+        match self.parse_any() {
+            Some(v) => visitor.visit_char(v),
+            None => Err(self.invalid_value(&"a character")),
+        }
     }
 
     fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        match self.parse_str() {
-            None => Err(de::Error::invalid_length(0, &"length > 0")),
-            Some(v) => visitor.visit_str(v),
+        match self.parse_borrowed_str() {
+            None => visitor.visit_str(""),
+            Some(Reference::Borrowed(v)) => visitor.visit_borrowed_str(v),
+            Some(Reference::Copied(v)) => visitor.visit_str(v),
         }
     }
 
@@ -258,11 +1063,14 @@ impl<'de, 'a, R: Read<'de>> de::Deserializer<'de> for &'a mut Deserializer<R> {
         self.deserialize_str(visitor)
     }
 
-    fn deserialize_bytes<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Err(de::Error::custom("unsupported bytes"))
+        match self.bytes_encoding.decode(&self.inner) {
+            Some(v) => visitor.visit_byte_buf(v),
+            None => Err(self.invalid_value(&"encoded bytes")),
+        }
     }
 
     fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
@@ -276,9 +1084,10 @@ impl<'de, 'a, R: Read<'de>> de::Deserializer<'de> for &'a mut Deserializer<R> {
     where
         V: Visitor<'de>,
     {
-        match self.parse_str() {
-            None => visitor.visit_none(),
-            Some(_) => visitor.visit_some(self),
+        if self.is_null() && self.children.is_none() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
         }
     }
 
@@ -286,9 +1095,10 @@ impl<'de, 'a, R: Read<'de>> de::Deserializer<'de> for &'a mut Deserializer<R> {
     where
         V: Visitor<'de>,
     {
-        match self.parse_str() {
-            None => visitor.visit_unit(),
-            Some(_) => Err(de::Error::invalid_type(Unexpected::Unit, &"unit")),
+        if self.is_null() {
+            visitor.visit_unit()
+        } else {
+            Err(self.invalid_value(&"unit"))
         }
     }
 
@@ -299,44 +1109,96 @@ impl<'de, 'a, R: Read<'de>> de::Deserializer<'de> for &'a mut Deserializer<R> {
         self.deserialize_unit(visitor)
     }
 
-    fn deserialize_newtype_struct<V>(self, _name: &'static str, _visitor: V) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Err(de::Error::custom("unsupported newtype struct"))
+        visitor.visit_newtype_struct(self)
     }
 
-    fn deserialize_seq<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        if let Some(collected) = self.collected.take() {
+            let mut entries = Vec::new();
+            for entry in collected {
+                self.load_value(entry);
+                entries.extend(self.split_value());
+            }
+            return visitor.visit_seq(EntrySeqAccess::new(self, entries));
+        }
+        if let Some(mut children) = self.children.take() {
+            let mut indices = Vec::with_capacity(children.len());
+            for child in &children {
+                match str::from_utf8(&child.key.0)
+                    .ok()
+                    .and_then(|k| k.parse().ok())
+                {
+                    Some(index) => indices.push(index),
+                    None => {
+                        let key = str::from_utf8(&child.key.0).unwrap_or_default();
+                        let err: Error =
+                            de::Error::invalid_value(Unexpected::Str(key), &"an index");
+                        return Err(err.prepend_key(key));
+                    }
+                }
+            }
+            let mut indexed: Vec<(usize, Node)> =
+                indices.into_iter().zip(children.drain(..)).collect();
+            indexed.sort_by_key(|&(index, _)| index);
+            let nodes = indexed.into_iter().map(|(_, node)| node).collect();
+            return visitor.visit_seq(NodeSeqAccess::new(self, nodes));
+        }
+        if self.in_map {
+            let entries = self.split_value();
+            return visitor.visit_seq(EntrySeqAccess::new(self, entries));
+        }
         Err(de::Error::custom("unsupported seq"))
     }
 
-    fn deserialize_tuple<V>(self, _len: usize, _visitor: V) -> Result<V::Value>
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Err(de::Error::custom("unsupported tuple"))
+        self.deserialize_seq(visitor)
     }
 
     fn deserialize_tuple_struct<V>(
         self,
         _name: &'static str,
         _len: usize,
-        _visitor: V,
+        visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Err(de::Error::custom("unsupported tuple struct"))
+        self.deserialize_seq(visitor)
     }
 
     fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_map(MapAccess::new(self))
+        if self.in_map {
+            return match self.children.take() {
+                Some(children) => visitor.visit_map(EntryMapAccess::new(self, children)),
+                None => {
+                    let unexp = Unexpected::Str(self.parse_str().unwrap_or(""));
+                    Err(de::Error::invalid_type(unexp, &visitor))
+                }
+            };
+        }
+
+        self.in_map = true;
+        let value = if self.duplicate_key == DuplicateKey::Visit && !self.dotted_keys {
+            visitor.visit_map(MapAccess::new(self))
+        } else {
+            let nodes = self.parse_entries()?;
+            visitor.visit_map(EntryMapAccess::new(self, nodes))
+        };
+        self.in_map = false;
+        value
     }
 
     fn deserialize_struct<V>(
@@ -360,7 +1222,7 @@ impl<'de, 'a, R: Read<'de>> de::Deserializer<'de> for &'a mut Deserializer<R> {
     where
         V: Visitor<'de>,
     {
-        visitor.visit_enum(UnitVariantAccess::new(self))
+        visitor.visit_enum(VariantAccess::new(self))
     }
 
     fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
@@ -395,39 +1257,168 @@ impl<'de, 'a, R: Read<'de> + 'a> de::MapAccess<'de> for MapAccess<'a, R> {
     where
         K: DeserializeSeed<'de>,
     {
-        let key = match self.de.parse_key() {
+        if !self.de.parse_key()? {
+            return Ok(None);
+        }
+
+        seed.deserialize(&mut *self.de)
+            .map(Some)
+            .map_err(|err| self.de.fix_position(err))
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        self.de.parse_value()?;
+        seed.deserialize(&mut *self.de).map_err(|err| {
+            let key = str::from_utf8(&self.de.key).unwrap_or_default();
+            self.de.fix_position(err.prepend_key(key))
+        })
+    }
+}
+
+// Visits keys buffered by `parse_entries`, each with the value picked by the
+// `DuplicateKey` policy.
+struct EntryMapAccess<'a, R: 'a> {
+    de: &'a mut Deserializer<R>,
+    nodes: vec::IntoIter<Node>,
+    key: Vec<u8>,
+    entries: Vec<Entry>,
+    children: Vec<Node>,
+}
+
+impl<'a, R: 'a> EntryMapAccess<'a, R> {
+    fn new(de: &'a mut Deserializer<R>, nodes: Vec<Node>) -> Self {
+        EntryMapAccess {
+            de,
+            nodes: nodes.into_iter(),
+            key: Vec::new(),
+            entries: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+}
+
+impl<'de, 'a, R: Read<'de> + 'a> de::MapAccess<'de> for EntryMapAccess<'a, R> {
+    type Error = error::Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        let node = match self.nodes.next() {
             None => return Ok(None),
-            Some(b) => b,
+            Some(node) => node,
         };
 
-        self.de.inner.clear();
-        self.de.inner.extend(key);
-        seed.deserialize(&mut *self.de).map(Some)
+        self.de.load_key(&node);
+        self.key = node.key.0;
+        self.entries = node.entries;
+        self.children = node.children;
+
+        seed.deserialize(&mut *self.de)
+            .map(Some)
+            .map_err(|err| self.de.fix_position(err))
     }
 
     fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
     where
         V: DeserializeSeed<'de>,
     {
-        let value = self.de.parse_value();
+        let entries = mem::take(&mut self.entries);
+        let children = mem::take(&mut self.children);
+        self.de.deserialize_node(&self.key, entries, children, seed)
+    }
+}
 
-        self.de.inner.clear();
-        self.de.inner.extend(value);
-        seed.deserialize(&mut *self.de)
+// Visits the keys nested under a key that are sequence indices, such as
+// `hosts.0` and `hosts.1`, in order.
+struct NodeSeqAccess<'a, R: 'a> {
+    de: &'a mut Deserializer<R>,
+    nodes: vec::IntoIter<Node>,
+}
+
+impl<'a, R: 'a> NodeSeqAccess<'a, R> {
+    fn new(de: &'a mut Deserializer<R>, nodes: Vec<Node>) -> Self {
+        NodeSeqAccess {
+            de,
+            nodes: nodes.into_iter(),
+        }
     }
 }
 
-struct UnitVariantAccess<'a, R: 'a> {
+impl<'de, 'a, R: Read<'de> + 'a> de::SeqAccess<'de> for NodeSeqAccess<'a, R> {
+    type Error = error::Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.nodes.next() {
+            None => Ok(None),
+            Some(node) => {
+                self.de.load_key(&node);
+                self.de
+                    .deserialize_node(&node.key.0, node.entries, node.children, seed)
+                    .map(Some)
+            }
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.nodes.len())
+    }
+}
+
+struct EntrySeqAccess<'a, R: 'a> {
+    de: &'a mut Deserializer<R>,
+    entries: vec::IntoIter<Entry>,
+}
+
+impl<'a, R: 'a> EntrySeqAccess<'a, R> {
+    fn new(de: &'a mut Deserializer<R>, entries: Vec<Entry>) -> Self {
+        EntrySeqAccess {
+            de,
+            entries: entries.into_iter(),
+        }
+    }
+}
+
+impl<'de, 'a, R: Read<'de> + 'a> de::SeqAccess<'de> for EntrySeqAccess<'a, R> {
+    type Error = error::Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.entries.next() {
+            None => Ok(None),
+            Some(entry) => {
+                self.de.load_value(entry);
+                seed.deserialize(&mut *self.de).map(Some)
+            }
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+// The value of a key names the variant. With dotted keys, the data of the
+// variant is read from the keys nested under it.
+struct VariantAccess<'a, R: 'a> {
     de: &'a mut Deserializer<R>,
 }
 
-impl<'a, R: 'a> UnitVariantAccess<'a, R> {
+impl<'a, R: 'a> VariantAccess<'a, R> {
     fn new(de: &'a mut Deserializer<R>) -> Self {
-        UnitVariantAccess { de }
+        VariantAccess { de }
     }
 }
 
-impl<'de, 'a, R: Read<'de> + 'a> de::EnumAccess<'de> for UnitVariantAccess<'a, R> {
+impl<'de, 'a, R: Read<'de> + 'a> de::EnumAccess<'de> for VariantAccess<'a, R> {
     type Error = error::Error;
     type Variant = Self;
 
@@ -440,41 +1431,65 @@ impl<'de, 'a, R: Read<'de> + 'a> de::EnumAccess<'de> for UnitVariantAccess<'a, R
     }
 }
 
-impl<'de, 'a, R: Read<'de> + 'a> de::VariantAccess<'de> for UnitVariantAccess<'a, R> {
+impl<'de, 'a, R: Read<'de> + 'a> de::VariantAccess<'de> for VariantAccess<'a, R> {
     type Error = error::Error;
 
     fn unit_variant(self) -> Result<()> {
         Ok(())
     }
 
-    fn newtype_variant_seed<T>(self, _seed: T) -> Result<T::Value>
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
     where
         T: DeserializeSeed<'de>,
     {
-        Err(de::Error::invalid_type(
-            Unexpected::UnitVariant,
-            &"newtype variant",
-        ))
+        let mut children = match self.de.children.take() {
+            Some(children) => children,
+            None => {
+                return Err(de::Error::invalid_type(
+                    Unexpected::UnitVariant,
+                    &"newtype variant",
+                ))
+            }
+        };
+
+        // Anything but a struct or map is written as `key.0`.
+        if children.len() == 1 && children[0].key.0 == b"0" {
+            let node = children.pop().unwrap();
+            self.de.load_key(&node);
+            return self
+                .de
+                .deserialize_node(&node.key.0, node.entries, node.children, seed);
+        }
+        self.de.inner.clear();
+        self.de.borrowed = None;
+        self.de.children = Some(children);
+        seed.deserialize(&mut *self.de)
     }
 
-    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value>
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Err(de::Error::invalid_type(
-            Unexpected::UnitVariant,
-            &"tuple variant",
-        ))
+        if self.de.children.is_none() {
+            return Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"tuple variant",
+            ));
+        }
+        de::Deserializer::deserialize_seq(self.de, visitor)
     }
 
-    fn struct_variant<V>(self, _fields: &'static [&'static str], _visitor: V) -> Result<V::Value>
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Err(de::Error::invalid_type(
-            Unexpected::UnitVariant,
-            &"struct variant",
-        ))
+        match self.de.children.take() {
+            Some(children) => visitor.visit_map(EntryMapAccess::new(self.de, children)),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"struct variant",
+            )),
+        }
     }
 }
 
@@ -490,6 +1505,15 @@ where
     Ok(value)
 }
 
+#[cfg(feature = "std")]
+pub fn from_reader<R, T>(reader: R) -> Result<T>
+where
+    R: std::io::Read,
+    T: de::DeserializeOwned,
+{
+    from_trait(IoRead::new(reader))
+}
+
 pub fn from_slice<'a, T: de::Deserialize<'a>>(v: &'a [u8]) -> Result<T> {
     from_trait(SliceRead::new(v))
 }
@@ -0,0 +1,392 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::fmt::{Display, Formatter};
+use core::mem;
+use core::str::FromStr;
+
+use serde::ser;
+
+use crate::de::{Deserializer, Dialect, Line};
+use crate::error::{Error, Result};
+use crate::ser::{format_escaped_str, to_string};
+
+/// A `.properties` file that keeps its comments, blank lines and formatting,
+/// so that it can be written back unchanged.
+#[derive(Clone, Debug, Default)]
+pub struct Document {
+    items: Vec<Item>,
+    dialect: Dialect,
+}
+
+#[derive(Clone, Debug)]
+enum Item {
+    // A blank or comment line, including its line ending.
+    Trivia(String),
+    Entry(Entry),
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    key: String,
+    value: String,
+    // The raw text of the logical line, split around the key and the value.
+    indent: String,
+    raw_key: String,
+    separator: String,
+    raw_value: String,
+    eol: String,
+}
+
+impl Document {
+    pub fn new() -> Self {
+        Document::default()
+    }
+
+    pub fn parse(s: &str, dialect: Dialect) -> Result<Self> {
+        let mut de = Deserializer::from_str(s).with_dialect(dialect);
+        let mut items = Vec::new();
+        let mut start = 0;
+        while let Some(line) = de.parse_line()? {
+            let end = de.byte_offset();
+            items.push(match line {
+                Line::Trivia => Item::Trivia(s[start..end].into()),
+                Line::Entry { key, value } => Item::Entry(Entry {
+                    key: de.key_str().into(),
+                    value: de.value_str().into(),
+                    indent: s[start..key.start].into(),
+                    raw_key: s[key.clone()].into(),
+                    separator: s[key.end..value.start].into(),
+                    raw_value: s[value.clone()].into(),
+                    eol: s[value.end..end].into(),
+                }),
+            });
+            start = end;
+        }
+        Ok(Document { items, dialect })
+    }
+
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+
+    /// Iterates over the keys and values of the entries in file order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.items.iter().filter_map(|item| match item {
+            Item::Entry(entry) => Some((&*entry.key, &*entry.value)),
+            Item::Trivia(_) => None,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    /// Returns the value of `key`. As with `java.util.Properties`, the last
+    /// occurrence of a repeated key wins.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        let index = self.position(key)?;
+        Some(&self.entry(index).value)
+    }
+
+    /// Replaces the value of `key` in place, returning the old one, or
+    /// appends a new entry if the key isn't present.
+    ///
+    /// Fails, leaving the document unchanged, if the line would read back
+    /// differently. Only a lenient document can't escape its way out of
+    /// that, for instance for a line break in a value or an `=` in a key.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<Option<String>> {
+        let index = match self.position(key) {
+            Some(index) => index,
+            None => {
+                let entry = self.new_entry(self.items.len(), key, value);
+                self.check(&entry)?;
+                self.insert_item(self.items.len(), entry);
+                return Ok(None);
+            }
+        };
+
+        let mut entry = self.entry(index).clone();
+        entry.raw_value = self.escape(value, false);
+        if entry.separator.is_empty() && !value.is_empty() {
+            entry.separator = "=".into();
+        }
+        let old = mem::replace(&mut entry.value, value.into());
+        self.check(&entry)?;
+        *self.entry_mut(index) = entry;
+        Ok(Some(old))
+    }
+
+    /// Inserts a new entry on the line after `anchor`. Returns `false`, and
+    /// leaves the document unchanged, if there is no such key. Fails as
+    /// [`set`](Document::set) does.
+    pub fn insert_after(&mut self, anchor: &str, key: &str, value: &str) -> Result<bool> {
+        let index = match self.position(anchor) {
+            Some(index) => index + 1,
+            None => return Ok(false),
+        };
+
+        let entry = self.new_entry(index, key, value);
+        self.check(&entry)?;
+        self.insert_item(index, entry);
+        Ok(true)
+    }
+
+    /// Removes every line that sets `key`, returning the value it had.
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let index = self.position(key)?;
+        let value = mem::take(&mut self.entry_mut(index).value);
+        self.items
+            .retain(|item| !matches!(item, Item::Entry(entry) if entry.key == key));
+        Some(value)
+    }
+
+    /// Renames every occurrence of `from` to `to`, keeping the values and the
+    /// rest of each line as they are. Fails as [`set`](Document::set) does.
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<bool> {
+        let raw_key = self.escape(to, true);
+        let mut renamed = Vec::new();
+        for (index, item) in self.items.iter().enumerate() {
+            if let Item::Entry(entry) = item {
+                if entry.key == from {
+                    let mut entry = entry.clone();
+                    entry.key = to.into();
+                    entry.raw_key.clone_from(&raw_key);
+                    self.check(&entry)?;
+                    renamed.push((index, entry));
+                }
+            }
+        }
+
+        let found = !renamed.is_empty();
+        for (index, entry) in renamed {
+            *self.entry_mut(index) = entry;
+        }
+        Ok(found)
+    }
+
+    fn position(&self, key: &str) -> Option<usize> {
+        self.items
+            .iter()
+            .rposition(|item| matches!(item, Item::Entry(entry) if entry.key == key))
+    }
+
+    fn entry(&self, index: usize) -> &Entry {
+        match &self.items[index] {
+            Item::Entry(entry) => entry,
+            Item::Trivia(_) => unreachable!(),
+        }
+    }
+
+    fn entry_mut(&mut self, index: usize) -> &mut Entry {
+        match &mut self.items[index] {
+            Item::Entry(entry) => entry,
+            Item::Trivia(_) => unreachable!(),
+        }
+    }
+
+    // Java needs escapes for what `Document::parse` would read differently.
+    // Lenient files only have a few, such as a trailing backslash, and no way
+    // to write them otherwise; `check` catches those.
+    fn escape(&self, s: &str, key: bool) -> String {
+        match self.dialect {
+            Dialect::Lenient => s.into(),
+            Dialect::Java => {
+                let mut raw = Vec::new();
+                // Writing to a `Vec` can't fail, and escapes are ASCII.
+                let _ = format_escaped_str(&mut raw, s, key);
+                String::from_utf8(raw).unwrap_or_default()
+            }
+        }
+    }
+
+    // Reads the line of `entry` back on its own, as the only reliable way to
+    // tell whether it still means what it says.
+    fn check(&self, entry: &Entry) -> Result<()> {
+        let line = format!(
+            "{}{}{}{}\n",
+            entry.indent, entry.raw_key, entry.separator, entry.raw_value
+        );
+        let doc = Document::parse(&line, self.dialect).unwrap_or_default();
+        let mut entries = doc.iter();
+        match (entries.next(), entries.next()) {
+            (Some((key, value)), None) if key == entry.key && value == entry.value => Ok(()),
+            _ => Err(Error::msg(format!(
+                "{:?} = {:?} does not read back unchanged in a {:?} document",
+                entry.key, entry.value, self.dialect
+            ))),
+        }
+    }
+
+    // A new entry copies the indentation and separator of the closest entry
+    // before it, or failing that, the first one in the file. A key with no
+    // value may have no separator at all, which a value can't do without.
+    fn new_entry(&self, index: usize, key: &str, value: &str) -> Entry {
+        let template = self.items[..index]
+            .iter()
+            .rev()
+            .chain(&self.items[index..])
+            .find_map(|item| match item {
+                Item::Entry(entry) => Some(entry),
+                Item::Trivia(_) => None,
+            });
+
+        let separator = template
+            .map(|entry| &*entry.separator)
+            .filter(|separator| !separator.is_empty() || value.is_empty())
+            .unwrap_or("=");
+
+        Entry {
+            key: key.into(),
+            value: value.into(),
+            indent: template.map_or("", |entry| &entry.indent).into(),
+            raw_key: self.escape(key, true),
+            separator: separator.into(),
+            raw_value: self.escape(value, false),
+            eol: self.line_ending().into(),
+        }
+    }
+
+    fn line_ending(&self) -> &str {
+        self.items
+            .iter()
+            .find_map(|item| {
+                ["\r\n", "\n", "\r"]
+                    .into_iter()
+                    .find(|eol| item.raw_eol().ends_with(eol))
+            })
+            .unwrap_or("\n")
+    }
+
+    fn insert_item(&mut self, index: usize, mut entry: Entry) {
+        // Only the last line can lack a line ending. It keeps the new line
+        // from being joined onto it, and passes the lack on to it.
+        if let Some(prev) = index.checked_sub(1).map(|i| &mut self.items[i]) {
+            if !prev.raw_eol().ends_with(['\r', '\n']) {
+                let eol = mem::take(&mut entry.eol);
+                prev.push_eol(&eol);
+            }
+        }
+        self.items.insert(index, Item::Entry(entry));
+    }
+}
+
+impl Item {
+    fn raw_eol(&self) -> &str {
+        match self {
+            Item::Trivia(raw) => raw,
+            Item::Entry(entry) => &entry.eol,
+        }
+    }
+
+    fn push_eol(&mut self, eol: &str) {
+        match self {
+            Item::Trivia(raw) => raw.push_str(eol),
+            Item::Entry(entry) => entry.eol.push_str(eol),
+        }
+    }
+}
+
+impl FromStr for Document {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Document::parse(s, Dialect::default())
+    }
+}
+
+impl Display for Document {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for item in &self.items {
+            match item {
+                Item::Trivia(raw) => f.write_str(raw)?,
+                Item::Entry(entry) => {
+                    f.write_str(&entry.indent)?;
+                    f.write_str(&entry.raw_key)?;
+                    f.write_str(&entry.separator)?;
+                    f.write_str(&entry.raw_value)?;
+                    f.write_str(&entry.eol)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Serializes a value into an existing [`Document`], updating the values of
+/// keys it already has in place and adding the others.
+pub struct Merge<'a> {
+    doc: &'a mut Document,
+    anchor: Option<&'a str>,
+    remove_missing: bool,
+}
+
+impl<'a> Merge<'a> {
+    pub fn new(doc: &'a mut Document) -> Self {
+        Merge {
+            doc,
+            anchor: None,
+            remove_missing: false,
+        }
+    }
+
+    /// Inserts new keys after `anchor` instead of at the end of the document.
+    pub fn with_anchor(mut self, anchor: &'a str) -> Self {
+        self.anchor = Some(anchor);
+        self
+    }
+
+    /// Removes keys that the value doesn't have.
+    pub fn with_remove_missing(mut self, remove_missing: bool) -> Self {
+        self.remove_missing = remove_missing;
+        self
+    }
+
+    pub fn merge<T>(self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        // Going through the text that `to_string` writes keeps every type and
+        // escape the `Serializer` supports, and `Document` re-escapes values
+        // for its own dialect.
+        let entries = Document::parse(&to_string(value)?, Dialect::Java)?;
+
+        // Edits a copy, so that the document is left as it was if any of them
+        // fails.
+        let mut doc = self.doc.clone();
+        if self.remove_missing {
+            doc.items.retain(|item| match item {
+                Item::Entry(entry) => entries.get(&entry.key).is_some(),
+                Item::Trivia(_) => true,
+            });
+        }
+
+        let mut anchor = self.anchor.filter(|&key| doc.get(key).is_some());
+        for (key, value) in entries.iter() {
+            if doc.get(key).is_some() {
+                doc.set(key, value)?;
+            } else if let Some(prev) = anchor {
+                doc.insert_after(prev, key, value)?;
+                anchor = Some(key);
+            } else {
+                doc.set(key, value)?;
+            }
+        }
+        *self.doc = doc;
+        Ok(())
+    }
+}
+
+/// Serializes `value` into `doc` with the default [`Merge`] settings: keys are
+/// updated in place, new keys go at the end and no key is removed.
+pub fn to_document_merge<T>(doc: &mut Document, value: &T) -> Result<()>
+where
+    T: ?Sized + ser::Serialize,
+{
+    Merge::new(doc).merge(value)
+}
@@ -0,0 +1,211 @@
+use alloc::vec::Vec;
+
+use crate::io;
+
+/// The character encoding of `.properties` bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    /// ISO-8859-1, the traditional encoding of Java `.properties` files. Any
+    /// character outside of it is written as a `\uXXXX` escape.
+    Latin1,
+}
+
+impl Encoding {
+    pub(crate) fn push_byte(self, slice: &mut Vec<u8>, b: u8) {
+        match self {
+            Encoding::Latin1 if !b.is_ascii() => {
+                slice.extend_from_slice(&[0xC0 | (b >> 6), 0x80 | (b & 0x3F)]);
+            }
+            _ => slice.push(b),
+        }
+    }
+}
+
+/// How byte strings are written as values.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BytesEncoding {
+    /// Two lowercase hex digits per byte. Either case is read.
+    #[default]
+    Hex,
+    /// Standard base64, padded with `=`. Padding is optional when read.
+    Base64,
+}
+
+static BASE64_DIGITS: [u8; 64] =
+    *b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+impl BytesEncoding {
+    pub(crate) fn encode<W>(self, writer: &mut W, bytes: &[u8]) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        static HEX_DIGITS: [u8; 16] = *b"0123456789abcdef";
+
+        let mut buf = Vec::with_capacity(bytes.len() * 2);
+        match self {
+            BytesEncoding::Hex => {
+                for &b in bytes {
+                    buf.extend_from_slice(&[
+                        HEX_DIGITS[(b >> 4) as usize],
+                        HEX_DIGITS[(b & 0xF) as usize],
+                    ]);
+                }
+            }
+            BytesEncoding::Base64 => {
+                for chunk in bytes.chunks(3) {
+                    let n = chunk
+                        .iter()
+                        .enumerate()
+                        .fold(0, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+                    for i in 0..4 {
+                        buf.push(if i <= chunk.len() {
+                            BASE64_DIGITS[(n >> (18 - 6 * i) & 0x3F) as usize]
+                        } else {
+                            b'='
+                        });
+                    }
+                }
+            }
+        }
+        writer.write_all(&buf)
+    }
+
+    pub(crate) fn decode(self, s: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            BytesEncoding::Hex => {
+                if !s.len().is_multiple_of(2) {
+                    return None;
+                }
+                s.chunks(2)
+                    .map(|pair| {
+                        let hi = (pair[0] as char).to_digit(16)?;
+                        let lo = (pair[1] as char).to_digit(16)?;
+                        Some((hi << 4 | lo) as u8)
+                    })
+                    .collect()
+            }
+            BytesEncoding::Base64 => {
+                let s = s
+                    .strip_suffix(b"==")
+                    .or_else(|| s.strip_suffix(b"="))
+                    .unwrap_or(s);
+                if s.len() % 4 == 1 {
+                    return None;
+                }
+                let mut bytes = Vec::with_capacity(s.len() * 3 / 4);
+                let (mut n, mut bits) = (0u32, 0);
+                for &c in s {
+                    let digit = BASE64_DIGITS.iter().position(|&d| d == c)?;
+                    n = n << 6 | digit as u32;
+                    bits += 6;
+                    if bits >= 8 {
+                        bits -= 8;
+                        bytes.push((n >> bits) as u8);
+                    }
+                }
+                Some(bytes)
+            }
+        }
+    }
+}
+
+pub(crate) fn write_unicode_escape<W>(writer: &mut W, unit: u16) -> io::Result<()>
+where
+    W: ?Sized + io::Write,
+{
+    static HEX_DIGITS: [u8; 16] = *b"0123456789ABCDEF";
+
+    writer.write_all(&[
+        b'\\',
+        b'u',
+        HEX_DIGITS[(unit >> 12) as usize],
+        HEX_DIGITS[(unit >> 8 & 0xF) as usize],
+        HEX_DIGITS[(unit >> 4 & 0xF) as usize],
+        HEX_DIGITS[(unit & 0xF) as usize],
+    ])
+}
+
+// Transcodes the UTF-8 written by a `Formatter` into the target encoding.
+pub(crate) struct EncodingWriter<W> {
+    writer: W,
+    encoding: Encoding,
+    // Holds back what is written, still in UTF-8, while set.
+    capture: Option<Vec<u8>>,
+}
+
+impl<W> EncodingWriter<W> {
+    pub fn new(writer: W) -> Self {
+        EncodingWriter {
+            writer,
+            encoding: Encoding::default(),
+            capture: None,
+        }
+    }
+
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.encoding = encoding;
+    }
+
+    pub fn begin_capture(&mut self) {
+        self.capture = Some(Vec::new());
+    }
+
+    pub fn end_capture(&mut self) -> Vec<u8> {
+        self.capture.take().unwrap_or_default()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: io::Write> EncodingWriter<W> {
+    fn write_latin1(&mut self, buf: &[u8]) -> io::Result<()> {
+        // Bytes that aren't valid UTF-8 are passed through unchanged.
+        for chunk in buf.utf8_chunks() {
+            let valid = chunk.valid();
+            let mut start = 0;
+            for (i, ch) in valid.char_indices() {
+                if ch.is_ascii() {
+                    continue;
+                }
+
+                self.writer.write_all(&valid.as_bytes()[start..i])?;
+                if let Ok(b) = u8::try_from(ch) {
+                    self.writer.write_all(&[b])?;
+                } else {
+                    for &mut unit in ch.encode_utf16(&mut [0; 2]) {
+                        write_unicode_escape(&mut self.writer, unit)?;
+                    }
+                }
+                start = i + ch.len_utf8();
+            }
+
+            self.writer.write_all(&valid.as_bytes()[start..])?;
+            self.writer.write_all(chunk.invalid())?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: io::Write> io::Write for EncodingWriter<W> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(capture) = &mut self.capture {
+            capture.extend_from_slice(buf);
+            return Ok(buf.len());
+        }
+        match self.encoding {
+            Encoding::Utf8 => self.writer.write_all(buf)?,
+            Encoding::Latin1 => self.write_latin1(buf)?,
+        }
+        Ok(buf.len())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
@@ -1,38 +1,197 @@
 use alloc::boxed::Box;
+use alloc::format;
 use alloc::string::{String, ToString};
 use core::fmt;
 use core::fmt::{Debug, Display, Formatter};
 use core::result;
 
+use crate::io;
+
 pub type Result<T> = result::Result<T, Error>;
 
 pub struct Error {
     err: Box<ErrorImpl>,
 }
 
+/// Categorizes the cause of a [`Error`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Category {
+    /// Failure to read or write bytes on an I/O stream.
+    Io,
+    /// Input that is not syntactically valid `.properties`.
+    Syntax,
+    /// Input that is syntactically valid but doesn't match the target type.
+    Data,
+    /// Input that ended in the middle of an escape sequence or a key.
+    Eof,
+}
+
 impl Error {
-    pub(crate) fn msg(msg: String) -> Self {
+    /// One-based line number at which the error was detected, or 0 if it
+    /// isn't tied to a position in the input.
+    pub fn line(&self) -> usize {
+        self.err.line
+    }
+
+    /// One-based column number at which the error was detected, or 0 if it
+    /// isn't tied to a position in the input.
+    pub fn column(&self) -> usize {
+        self.err.column
+    }
+
+    /// Byte offset into the input at which the error was detected.
+    pub fn offset(&self) -> usize {
+        self.err.offset
+    }
+
+    /// The key whose value failed to deserialize, if any. Keys of enclosing
+    /// maps are prepended to it as a dotted path.
+    pub fn key(&self) -> Option<&str> {
+        self.err.key.as_deref()
+    }
+
+    pub fn classify(&self) -> Category {
+        match self.err.code {
+            ErrorCode::Message(_) | ErrorCode::DuplicateKey(_) => Category::Data,
+            ErrorCode::Io(_) => Category::Io,
+            ErrorCode::EofWhileParsingEscape | ErrorCode::EofWhileParsingKey => Category::Eof,
+            ErrorCode::ExpectedSeparator
+            | ErrorCode::InvalidEscape
+            | ErrorCode::InvalidUtf8
+            | ErrorCode::LoneSurrogate
+            | ErrorCode::TrailingCharacters => Category::Syntax,
+        }
+    }
+
+    pub fn is_io(&self) -> bool {
+        self.classify() == Category::Io
+    }
+
+    pub fn is_syntax(&self) -> bool {
+        self.classify() == Category::Syntax
+    }
+
+    pub fn is_data(&self) -> bool {
+        self.classify() == Category::Data
+    }
+
+    pub fn is_eof(&self) -> bool {
+        self.classify() == Category::Eof
+    }
+}
+
+pub(crate) enum ErrorCode {
+    Message(Box<str>),
+    Io(io::Error),
+    /// The line on which the key first appeared.
+    DuplicateKey(usize),
+    EofWhileParsingEscape,
+    EofWhileParsingKey,
+    ExpectedSeparator,
+    InvalidEscape,
+    InvalidUtf8,
+    LoneSurrogate,
+    TrailingCharacters,
+}
+
+impl Error {
+    fn new(code: ErrorCode) -> Self {
         Error {
             err: Box::new(ErrorImpl {
-                msg: msg.into_boxed_str(),
+                code,
+                key: None,
+                line: 0,
+                column: 0,
+                offset: 0,
             }),
         }
     }
+
+    pub(crate) fn syntax(code: ErrorCode, line: usize, column: usize, offset: usize) -> Self {
+        Error::new(code).fix_position(line, column, offset)
+    }
+
+    pub(crate) fn io(error: io::Error) -> Self {
+        Error::new(ErrorCode::Io(error))
+    }
+
+    pub(crate) fn msg(msg: String) -> Self {
+        Error::new(ErrorCode::Message(msg.into_boxed_str()))
+    }
+
+    // Errors bubble up through each enclosing map, which adds its own key in
+    // front of the path built so far.
+    // Whether a lenient line ended before a separator.
+    pub(crate) fn is_missing_separator(&self) -> bool {
+        matches!(
+            self.err.code,
+            ErrorCode::ExpectedSeparator | ErrorCode::EofWhileParsingKey
+        )
+    }
+
+    pub(crate) fn prepend_key(mut self, key: &str) -> Self {
+        self.err.key = Some(match self.err.key.take() {
+            Some(path) => format!("{}.{}", key, path).into_boxed_str(),
+            None => key.into(),
+        });
+        self
+    }
+
+    pub(crate) fn fix_position(mut self, line: usize, column: usize, offset: usize) -> Self {
+        if self.err.line == 0 {
+            self.err.line = line;
+            self.err.column = column;
+            self.err.offset = offset;
+        }
+        self
+    }
 }
 
 struct ErrorImpl {
-    msg: Box<str>,
+    code: ErrorCode,
+    key: Option<Box<str>>,
+    line: usize,
+    column: usize,
+    offset: usize,
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ErrorCode::Message(msg) => f.write_str(msg),
+            ErrorCode::Io(err) => Display::fmt(err, f),
+            ErrorCode::DuplicateKey(line) => write!(f, "duplicate key, first set at line {}", line),
+            ErrorCode::EofWhileParsingEscape => f.write_str("EOF while parsing an escape"),
+            ErrorCode::EofWhileParsingKey => f.write_str("EOF while parsing a key"),
+            ErrorCode::ExpectedSeparator => f.write_str("expected `=` or `:` after key"),
+            ErrorCode::InvalidEscape => f.write_str("malformed \\uxxxx encoding"),
+            ErrorCode::InvalidUtf8 => f.write_str("invalid UTF-8"),
+            ErrorCode::LoneSurrogate => f.write_str("lone surrogate in \\uxxxx escape"),
+            ErrorCode::TrailingCharacters => f.write_str("trailing characters"),
+        }
+    }
 }
 
 impl Display for ErrorImpl {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        f.write_str(&self.msg)
+        if let Some(key) = &self.key {
+            write!(f, "{}: ", key)?;
+        }
+        Display::fmt(&self.code, f)?;
+        if self.line != 0 {
+            write!(f, " at line {} column {}", self.line, self.column)?;
+        }
+        Ok(())
     }
 }
 
 impl Debug for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "Error({:?})", self.err.to_string())
+        write!(f, "Error({:?}, ", self.err.code.to_string())?;
+        if let Some(key) = &self.err.key {
+            write!(f, "key: {:?}, ", key)?;
+        }
+        write!(f, "line: {}, column: {})", self.err.line, self.err.column)
     }
 }
 
@@ -42,7 +201,15 @@ impl Display for Error {
     }
 }
 
-impl serde::de::StdError for Error {}
+impl serde::de::StdError for Error {
+    #[cfg(feature = "std")]
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.err.code {
+            ErrorCode::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
 
 impl serde::de::Error for Error {
     fn custom<T: Display>(msg: T) -> Self {
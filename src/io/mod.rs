@@ -1,4 +1,4 @@
-pub use self::io::{Result, Write};
+pub use self::io::{Error, Result, Write};
 
 #[cfg(not(feature = "std"))]
 #[path = "core.rs"]
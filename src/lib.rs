@@ -6,12 +6,28 @@
 
 extern crate alloc;
 
-pub use crate::de::{from_slice, from_str, Deserializer};
-pub use crate::error::{Error, Result};
-pub use crate::ser::{to_string, to_vec, to_writer, Serializer};
+#[cfg(feature = "std")]
+pub use crate::de::from_reader;
+pub use crate::de::{from_slice, from_str, Deserializer, Dialect, DuplicateKey};
+pub use crate::document::{to_document_merge, Document, Merge};
+pub use crate::encoding::{BytesEncoding, Encoding};
+pub use crate::error::{Category, Error, Result};
+pub use crate::map::Map;
+#[cfg(feature = "std")]
+pub use crate::read::IoRead;
+pub use crate::read::{Position, Read, SliceRead, StrRead};
+pub use crate::ser::{
+    to_string, to_string_pretty, to_vec, to_vec_pretty, to_writer, to_writer_pretty, CommentMarker,
+    CompactFormatter, Formatter, KeySeparator, PrettyFormatter, Serializer,
+};
+pub use crate::value::{from_value, to_value, Value};
 
 mod de;
+mod document;
+mod encoding;
 mod error;
 mod io;
+pub mod map;
 mod read;
 mod ser;
+pub mod value;
@@ -0,0 +1,312 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::fmt;
+use core::ops;
+use core::slice;
+
+use serde::de;
+use serde::ser;
+use serde::ser::SerializeMap;
+
+use crate::value::Value;
+
+/// A map of keys to values that iterates in insertion order, which is the
+/// order in which they appear in a file.
+pub struct Map<K, V> {
+    entries: Vec<(K, V)>,
+    // Index of each key into `entries`.
+    indices: BTreeMap<K, usize>,
+}
+
+impl Map<String, Value> {
+    pub fn new() -> Self {
+        Map {
+            entries: Vec::new(),
+            indices: BTreeMap::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.indices.clear();
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&Value>
+    where
+        String: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        let &index = self.indices.get(key)?;
+        Some(&self.entries[index].1)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut Value>
+    where
+        String: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        let &index = self.indices.get(key)?;
+        Some(&mut self.entries[index].1)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        String: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.indices.contains_key(key)
+    }
+
+    /// Inserts a key-value pair, returning the previous value of the key. A
+    /// key that is already present keeps its position.
+    pub fn insert(&mut self, key: String, value: Value) -> Option<Value> {
+        if let Some(value_mut) = self.get_mut(&key) {
+            return Some(core::mem::replace(value_mut, value));
+        }
+
+        self.indices.insert(key.clone(), self.entries.len());
+        self.entries.push((key, value));
+        None
+    }
+
+    /// Removes a key, shifting the entries after it, and returns its value.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<Value>
+    where
+        String: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        let index = self.indices.remove(key)?;
+        let (_, value) = self.entries.remove(index);
+        for i in self.indices.values_mut() {
+            if *i > index {
+                *i -= 1;
+            }
+        }
+        Some(value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            iter: self.entries.iter(),
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_> {
+        IterMut {
+            iter: self.entries.iter_mut(),
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.entries.iter().map(|(_, value)| value)
+    }
+}
+
+impl Default for Map<String, Value> {
+    fn default() -> Self {
+        Map::new()
+    }
+}
+
+impl Clone for Map<String, Value> {
+    fn clone(&self) -> Self {
+        Map {
+            entries: self.entries.clone(),
+            indices: self.indices.clone(),
+        }
+    }
+}
+
+impl PartialEq for Map<String, Value> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl fmt::Debug for Map<String, Value> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<Q> ops::Index<&Q> for Map<String, Value>
+where
+    String: Borrow<Q>,
+    Q: ?Sized + Ord,
+{
+    type Output = Value;
+
+    fn index(&self, key: &Q) -> &Value {
+        self.get(key).expect("key not found in Map")
+    }
+}
+
+impl<Q> ops::IndexMut<&Q> for Map<String, Value>
+where
+    String: Borrow<Q>,
+    Q: ?Sized + Ord,
+{
+    fn index_mut(&mut self, key: &Q) -> &mut Value {
+        self.get_mut(key).expect("key not found in Map")
+    }
+}
+
+impl FromIterator<(String, Value)> for Map<String, Value> {
+    fn from_iter<T>(iter: T) -> Self
+    where
+        T: IntoIterator<Item = (String, Value)>,
+    {
+        let mut map = Map::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl Extend<(String, Value)> for Map<String, Value> {
+    fn extend<T>(&mut self, iter: T)
+    where
+        T: IntoIterator<Item = (String, Value)>,
+    {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+pub struct Iter<'a> {
+    iter: slice::Iter<'a, (String, Value)>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a String, &'a Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|(key, value)| (key, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+pub struct IterMut<'a> {
+    iter: slice::IterMut<'a, (String, Value)>,
+}
+
+impl<'a> Iterator for IterMut<'a> {
+    type Item = (&'a String, &'a mut Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|(key, value)| (&*key, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+pub struct IntoIter {
+    iter: vec::IntoIter<(String, Value)>,
+}
+
+impl Iterator for IntoIter {
+    type Item = (String, Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a> IntoIterator for &'a Map<String, Value> {
+    type Item = (&'a String, &'a Value);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Map<String, Value> {
+    type Item = (&'a String, &'a mut Value);
+    type IntoIter = IterMut<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl IntoIterator for Map<String, Value> {
+    type Item = (String, Value);
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            iter: self.entries.into_iter(),
+        }
+    }
+}
+
+impl ser::Serialize for Map<String, Value> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (key, value) in self {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> de::Deserialize<'de> for Map<String, Value> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct MapVisitor;
+
+        impl<'de> de::Visitor<'de> for MapVisitor {
+            type Value = Map<String, Value>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a map")
+            }
+
+            fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                let mut map = Map::new();
+                while let Some((key, value)) = access.next_entry()? {
+                    map.insert(key, value);
+                }
+                Ok(map)
+            }
+        }
+
+        deserializer.deserialize_map(MapVisitor)
+    }
+}
@@ -1,56 +1,143 @@
 use core::str;
 
+#[cfg(feature = "std")]
+use std::io;
+
+#[cfg(feature = "std")]
+use crate::error::Error;
+use crate::error::Result;
+
+/// Trait used by the deserializer for iterating over input.
 pub trait Read<'de> {
-    fn next(&mut self) -> Option<u8>;
-    fn peek(&mut self) -> Option<u8>;
+    /// Consumes and returns the next byte, or `None` at the end of input.
+    fn next(&mut self) -> Result<Option<u8>>;
+
+    /// Returns the next byte without consuming it.
+    fn peek(&mut self) -> Result<Option<u8>>;
+
+    /// Consumes the byte returned by the last call to `peek`.
     fn discard(&mut self);
+
+    /// Position of the most recently consumed byte.
+    fn position(&self) -> Position;
+
+    /// Position of the byte that `peek` returns.
+    fn peek_position(&self) -> Position;
+
+    /// Number of bytes consumed so far.
     fn byte_offset(&self) -> usize;
+
+    /// Returns the input between two byte offsets if it can be borrowed for
+    /// the `'de` lifetime. Readers that don't keep their input around return
+    /// `None`, which makes every string an owned copy.
+    fn input_slice(&self, start: usize, end: usize) -> Option<&'de [u8]> {
+        let _ = (start, end);
+        None
+    }
 }
 
+pub(crate) enum Reference<'b, 'c, T: ?Sized> {
+    Borrowed(&'b T),
+    Copied(&'c T),
+}
+
+/// One-based line and column numbers in the input.
+#[derive(Clone, Copy, Debug)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Input from an in-memory byte slice.
 pub struct SliceRead<'a> {
     slice: &'a [u8],
     index: usize,
+    line: usize,
+    // Index of the first byte of the current line.
+    line_start: usize,
 }
 
+/// Input from an in-memory string.
 pub struct StrRead<'a> {
     delegate: SliceRead<'a>,
 }
 
 impl<'a> SliceRead<'a> {
     pub fn new(slice: &'a [u8]) -> Self {
-        SliceRead { slice, index: 0 }
+        SliceRead {
+            slice,
+            index: 0,
+            line: 1,
+            line_start: 0,
+        }
+    }
+
+    fn consume(&mut self, ch: u8) {
+        self.index += 1;
+        if ch == b'\n' {
+            self.line += 1;
+            self.line_start = self.index;
+        }
     }
 }
 
 impl<'a> Read<'a> for SliceRead<'a> {
     #[inline]
-    fn next(&mut self) -> Option<u8> {
-        if self.index < self.slice.len() {
+    fn next(&mut self) -> Result<Option<u8>> {
+        Ok(if self.index < self.slice.len() {
             let ch = self.slice[self.index];
-            self.index += 1;
+            self.consume(ch);
             Some(ch)
         } else {
             None
-        }
+        })
     }
 
     #[inline]
-    fn peek(&mut self) -> Option<u8> {
-        if self.index < self.slice.len() {
+    fn peek(&mut self) -> Result<Option<u8>> {
+        Ok(if self.index < self.slice.len() {
             Some(self.slice[self.index])
         } else {
             None
-        }
+        })
     }
 
     #[inline]
     fn discard(&mut self) {
-        self.index += 1;
+        if let Some(&ch) = self.slice.get(self.index) {
+            self.consume(ch);
+        }
+    }
+
+    fn position(&self) -> Position {
+        Position {
+            line: self.line,
+            column: self.index - self.line_start,
+        }
+    }
+
+    fn peek_position(&self) -> Position {
+        match self.slice.get(self.index) {
+            Some(b'\n') => Position {
+                line: self.line + 1,
+                column: 0,
+            },
+            Some(_) => Position {
+                line: self.line,
+                column: self.index + 1 - self.line_start,
+            },
+            // Nothing left to peek, as in `position`.
+            None => self.position(),
+        }
     }
 
     fn byte_offset(&self) -> usize {
         self.index
     }
+
+    fn input_slice(&self, start: usize, end: usize) -> Option<&'a [u8]> {
+        self.slice.get(start..end)
+    }
 }
 
 impl<'a> StrRead<'a> {
@@ -63,12 +150,12 @@ impl<'a> StrRead<'a> {
 
 impl<'a> Read<'a> for StrRead<'a> {
     #[inline]
-    fn next(&mut self) -> Option<u8> {
+    fn next(&mut self) -> Result<Option<u8>> {
         self.delegate.next()
     }
 
     #[inline]
-    fn peek(&mut self) -> Option<u8> {
+    fn peek(&mut self) -> Result<Option<u8>> {
         self.delegate.peek()
     }
 
@@ -77,7 +164,117 @@ impl<'a> Read<'a> for StrRead<'a> {
         self.delegate.discard();
     }
 
+    fn position(&self) -> Position {
+        self.delegate.position()
+    }
+
+    fn peek_position(&self) -> Position {
+        self.delegate.peek_position()
+    }
+
     fn byte_offset(&self) -> usize {
         self.delegate.byte_offset()
     }
+
+    fn input_slice(&self, start: usize, end: usize) -> Option<&'a [u8]> {
+        self.delegate.input_slice(start, end)
+    }
+}
+
+/// Input from a `std::io::Read`, one byte at a time.
+///
+/// Reading from an unbuffered source such as a `File` is slow; wrap it in a
+/// `std::io::BufReader` first.
+#[cfg(feature = "std")]
+pub struct IoRead<R> {
+    bytes: io::Bytes<R>,
+    peeked: Option<u8>,
+    line: usize,
+    column: usize,
+    offset: usize,
+}
+
+#[cfg(feature = "std")]
+impl<R: io::Read> IoRead<R> {
+    // Buffering is left to the caller, as documented above.
+    #[allow(clippy::unbuffered_bytes)]
+    pub fn new(reader: R) -> Self {
+        IoRead {
+            bytes: reader.bytes(),
+            peeked: None,
+            line: 1,
+            column: 0,
+            offset: 0,
+        }
+    }
+
+    fn consume(&mut self, ch: u8) {
+        self.offset += 1;
+        if ch == b'\n' {
+            self.line += 1;
+            self.column = 0;
+        } else {
+            self.column += 1;
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de, R: io::Read> Read<'de> for IoRead<R> {
+    #[inline]
+    fn next(&mut self) -> Result<Option<u8>> {
+        let ch = match self.peeked.take() {
+            Some(ch) => ch,
+            None => match self.bytes.next() {
+                Some(Ok(ch)) => ch,
+                Some(Err(err)) => return Err(Error::io(err)),
+                None => return Ok(None),
+            },
+        };
+        self.consume(ch);
+        Ok(Some(ch))
+    }
+
+    #[inline]
+    fn peek(&mut self) -> Result<Option<u8>> {
+        if self.peeked.is_none() {
+            self.peeked = match self.bytes.next() {
+                Some(Ok(ch)) => Some(ch),
+                Some(Err(err)) => return Err(Error::io(err)),
+                None => None,
+            };
+        }
+        Ok(self.peeked)
+    }
+
+    #[inline]
+    fn discard(&mut self) {
+        if let Some(ch) = self.peeked.take() {
+            self.consume(ch);
+        }
+    }
+
+    fn position(&self) -> Position {
+        Position {
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn peek_position(&self) -> Position {
+        match self.peeked {
+            Some(b'\n') => Position {
+                line: self.line + 1,
+                column: 0,
+            },
+            _ => Position {
+                line: self.line,
+                column: self.column + 1,
+            },
+        }
+    }
+
+    fn byte_offset(&self) -> usize {
+        self.offset
+    }
 }
@@ -1,33 +1,348 @@
+use alloc::collections::BTreeMap;
 use alloc::string::String;
 use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::{mem, str};
 
 use serde::ser;
+use serde::ser::Impossible;
 
+use crate::encoding::{write_unicode_escape, BytesEncoding, Encoding, EncodingWriter};
 use crate::error;
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::io;
 
 pub struct Serializer<W, F = CompactFormatter> {
-    writer: W,
+    writer: EncodingWriter<W>,
     formatter: F,
+    line_width: Option<usize>,
+    // The formatted key of the value being serialized. Keys of nested maps
+    // and structs are joined to it with dots, and it is written out when a
+    // scalar value is reached.
+    path: Vec<u8>,
+    // Number of maps and structs being serialized.
+    depth: usize,
+    first: bool,
+    seq_separator: char,
+    indexed_seqs: bool,
+    skip_none: bool,
+    null_token: Option<String>,
+    // Set while the elements of a sequence are written as a single value,
+    // to whether the next element is the first.
+    element: Option<bool>,
+    // Set while the data of a newtype variant is serialized, to the length
+    // of the path without its trailing `.0`.
+    variant: Option<usize>,
+    // The part of the last key before its first dot, if it has one.
+    group: Option<Vec<u8>>,
+    // Keys and values held back until the end of the file, when the
+    // formatter aligns keys or they are reordered.
+    pending: Vec<(Vec<u8>, Vec<u8>)>,
+    order: KeyOrder,
+    header: Option<String>,
+    // Comments by the formatted key they go above.
+    comments: BTreeMap<Vec<u8>, String>,
+    comment_marker: CommentMarker,
 }
 
 impl<W: io::Write, F: Formatter> Serializer<W, F> {
     #[inline]
     pub fn with_formatter(writer: W, formatter: F) -> Self {
-        Serializer { writer, formatter }
+        Serializer {
+            writer: EncodingWriter::new(writer),
+            formatter,
+            line_width: None,
+            path: Vec::new(),
+            depth: 0,
+            first: true,
+            seq_separator: ',',
+            indexed_seqs: false,
+            skip_none: false,
+            null_token: None,
+            element: None,
+            variant: None,
+            group: None,
+            pending: Vec::new(),
+            order: KeyOrder::Serialized,
+            header: None,
+            comments: BTreeMap::new(),
+            comment_marker: CommentMarker::default(),
+        }
+    }
+
+    #[inline]
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.writer.set_encoding(encoding);
+        self
+    }
+
+    /// Continues string values longer than `width` bytes on the next line,
+    /// breaking them after whitespace.
+    #[inline]
+    pub fn with_line_width(mut self, width: usize) -> Self {
+        self.line_width = Some(width);
+        self
+    }
+
+    /// Sets what separates the elements of a sequence written as a single
+    /// value, `,` by default. String elements that are empty, contain it, or
+    /// start or end with a space can't be read back and fail to serialize;
+    /// [`with_indexed_seqs`](Serializer::with_indexed_seqs) writes those.
+    #[inline]
+    pub fn with_seq_separator(mut self, separator: char) -> Self {
+        self.seq_separator = separator;
+        self
+    }
+
+    /// Writes each element of a sequence under its own indexed key, as
+    /// `hosts.0=a`, instead of joining them into a single value.
+    #[inline]
+    pub fn with_indexed_seqs(mut self, indexed: bool) -> Self {
+        self.indexed_seqs = indexed;
+        self
+    }
+
+    /// Leaves out keys whose value is `None`, rather than writing `key=`.
+    #[inline]
+    pub fn with_skip_none(mut self, skip_none: bool) -> Self {
+        self.skip_none = skip_none;
+        self
+    }
+
+    /// Writes `None` and `()` as `token`, such as `null`, rather than as an
+    /// empty value.
+    #[inline]
+    pub fn with_null_token<S: Into<String>>(mut self, token: S) -> Self {
+        self.null_token = Some(token.into());
+        self
+    }
+
+    /// Starts the file with a comment, set apart from the first key by a
+    /// blank line.
+    #[inline]
+    pub fn with_header<S: Into<String>>(mut self, header: S) -> Self {
+        self.header = Some(header.into());
+        self
+    }
+
+    /// Writes a comment above each of the given keys. Nested keys are given
+    /// as they are written, such as `server.port`.
+    pub fn with_comments<I, K, V>(mut self, comments: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: Into<String>,
+    {
+        for (key, comment) in comments {
+            self.comments
+                .insert(format_key(key.as_ref()), comment.into());
+        }
+        self
+    }
+
+    /// Writes keys sorted by each of their segments in turn, comparing
+    /// segments that are numbers as numbers.
+    #[inline]
+    pub fn with_sorted_keys(mut self, sorted: bool) -> Self {
+        self.order = if sorted {
+            KeyOrder::Sorted
+        } else {
+            KeyOrder::Serialized
+        };
+        self
+    }
+
+    /// Writes keys in the given order. A key also places the keys nested
+    /// under it, so `server` places `server.host` and `server.port`. Keys
+    /// that aren't listed follow in the order they were serialized.
+    pub fn with_key_order<I, K>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        K: AsRef<str>,
+    {
+        let keys = keys.into_iter().map(|key| format_key(key.as_ref()));
+        self.order = KeyOrder::Custom(keys.collect());
+        self
+    }
+
+    #[inline]
+    pub fn with_comment_marker(mut self, marker: CommentMarker) -> Self {
+        self.comment_marker = marker;
+        self
     }
 
     #[inline]
     pub fn into_inner(self) -> W {
-        self.writer
+        self.writer.into_inner()
     }
 }
 
 impl<W: io::Write> Serializer<W> {
     #[inline]
     pub fn new(writer: W) -> Self {
-        Serializer::with_formatter(writer, CompactFormatter)
+        Serializer::with_formatter(writer, CompactFormatter::new())
+    }
+}
+
+impl<W: io::Write, F: Formatter> Serializer<W, F> {
+    fn begin_entry(&mut self) -> Result<()> {
+        self.variant = None;
+        if let Some(first) = self.element {
+            self.element = Some(false);
+            if !first {
+                let mut buf = [0; 4];
+                let separator = self.seq_separator.encode_utf8(&mut buf);
+                io::Write::write_all(&mut self.writer, separator.as_bytes()).map_err(Error::io)?;
+            }
+            return Ok(());
+        }
+        if self.depth == 0 {
+            return Err(top_level_must_be_a_map());
+        }
+
+        if self.holds_entries() {
+            self.pending.push((self.path.clone(), Vec::new()));
+            self.writer.begin_capture();
+            return Ok(());
+        }
+        let path = mem::take(&mut self.path);
+        let result = self.write_key(&path, 0);
+        self.path = path;
+        result
+    }
+
+    fn end_entry(&mut self) -> Result<()> {
+        if self.element.is_some() {
+            return Ok(());
+        }
+
+        self.formatter
+            .end_value(&mut self.writer)
+            .map_err(Error::io)?;
+        if self.holds_entries() {
+            let value = self.writer.end_capture();
+            self.pending.last_mut().unwrap().1 = value;
+        }
+        Ok(())
+    }
+
+    fn holds_entries(&self) -> bool {
+        self.formatter.align_keys() || !matches!(self.order, KeyOrder::Serialized)
+    }
+
+    // Writes everything of an entry that comes before its value.
+    fn write_key(&mut self, key: &[u8], padding: usize) -> Result<()> {
+        let group = key.iter().position(|&b| b == b'.').map(|i| &key[..i]);
+        if self.first {
+            self.first = false;
+            if let Some(header) = self.header.take() {
+                self.write_comment(&header)?;
+                self.formatter
+                    .end_comment(&mut self.writer)
+                    .map_err(Error::io)?;
+            }
+        } else {
+            self.formatter
+                .begin_key(&mut self.writer)
+                .map_err(Error::io)?;
+            if group != self.group.as_deref() {
+                self.formatter
+                    .begin_group(&mut self.writer)
+                    .map_err(Error::io)?;
+            }
+        }
+        self.group = group.map(<[u8]>::to_vec);
+        if let Some(comment) = self.comments.remove(key) {
+            self.write_comment(&comment)?;
+        }
+
+        io::Write::write_all(&mut self.writer, key).map_err(Error::io)?;
+        self.formatter
+            .write_key_padding(&mut self.writer, padding)
+            .map_err(Error::io)?;
+        self.formatter
+            .end_key(&mut self.writer)
+            .map_err(Error::io)?;
+        self.formatter
+            .begin_value(&mut self.writer)
+            .map_err(Error::io)
+    }
+
+    // Writes out held back entries once the top-level map is done.
+    fn end_document(&mut self) -> Result<()> {
+        fn width(key: &[u8]) -> usize {
+            str::from_utf8(key).map_or(key.len(), |key| key.chars().count())
+        }
+
+        let mut pending = mem::take(&mut self.pending);
+        match &self.order {
+            KeyOrder::Serialized => {}
+            KeyOrder::Sorted => pending.sort_by(|(a, _), (b, _)| compare_keys(a, b)),
+            KeyOrder::Custom(keys) => pending.sort_by_key(|(key, _)| {
+                keys.iter()
+                    .position(|k| {
+                        key.starts_with(k) && matches!(key.get(k.len()), None | Some(b'.'))
+                    })
+                    .unwrap_or(keys.len())
+            }),
+        }
+        let max = if self.formatter.align_keys() {
+            pending.iter().map(|(key, _)| width(key)).max()
+        } else {
+            None
+        };
+        for (key, value) in pending {
+            let padding = max.map_or(0, |max| max - width(&key));
+            self.write_key(&key, padding)?;
+            io::Write::write_all(&mut self.writer, &value).map_err(Error::io)?;
+        }
+
+        if self.first {
+            // A file without keys still gets its header.
+            return match self.header.take() {
+                Some(header) => self.write_comment(&header),
+                None => Ok(()),
+            };
+        }
+        self.formatter
+            .end_document(&mut self.writer)
+            .map_err(Error::io)
+    }
+
+    // Writes a comment line for each line of `comment`, so that none of it
+    // can be read back as a key.
+    fn write_comment(&mut self, comment: &str) -> Result<()> {
+        let comment = comment.trim_end_matches(['\r', '\n']);
+        let lines = comment
+            .split('\n')
+            .flat_map(|line| line.strip_suffix('\r').unwrap_or(line).split('\r'));
+        for line in lines {
+            self.formatter
+                .write_comment(&mut self.writer, self.comment_marker, line)
+                .map_err(Error::io)?;
+            self.formatter
+                .end_comment(&mut self.writer)
+                .map_err(Error::io)?;
+        }
+        Ok(())
+    }
+
+    // Nests the keys that follow under the current one.
+    fn begin_nested(&mut self) -> usize {
+        if self.depth > 0 {
+            self.path.push(b'.');
+        }
+        self.depth += 1;
+        self.path.len()
+    }
+
+    fn write_scalar<G>(&mut self, write: G) -> Result<()>
+    where
+        G: FnOnce(&mut F, &mut EncodingWriter<W>) -> io::Result<()>,
+    {
+        self.begin_entry()?;
+        write(&mut self.formatter, &mut self.writer).map_err(Error::io)?;
+        self.end_entry()
     }
 }
 
@@ -45,10 +360,11 @@ pub trait Formatter {
     where
         W: ?Sized + io::Write,
     {
-        let s = if value {
-            b"true" as &[u8]
-        } else {
-            b"false" as &[u8]
+        let s = match (value, self.numeric_bools()) {
+            (true, false) => b"true" as &[u8],
+            (false, false) => b"false" as &[u8],
+            (true, true) => b"1" as &[u8],
+            (false, true) => b"0" as &[u8],
         };
         writer.write_all(s)
     }
@@ -58,6 +374,9 @@ pub trait Formatter {
     where
         W: ?Sized + io::Write,
     {
+        if self.hex_integers() {
+            return format_hex(writer, value < 0, value.unsigned_abs().into());
+        }
         let mut buffer = itoa::Buffer::new();
         let s = buffer.format(value);
         writer.write_all(s.as_bytes())
@@ -68,6 +387,9 @@ pub trait Formatter {
     where
         W: ?Sized + io::Write,
     {
+        if self.hex_integers() {
+            return format_hex(writer, value < 0, value.unsigned_abs().into());
+        }
         let mut buffer = itoa::Buffer::new();
         let s = buffer.format(value);
         writer.write_all(s.as_bytes())
@@ -78,6 +400,9 @@ pub trait Formatter {
     where
         W: ?Sized + io::Write,
     {
+        if self.hex_integers() {
+            return format_hex(writer, value < 0, value.unsigned_abs().into());
+        }
         let mut buffer = itoa::Buffer::new();
         let s = buffer.format(value);
         writer.write_all(s.as_bytes())
@@ -88,6 +413,9 @@ pub trait Formatter {
     where
         W: ?Sized + io::Write,
     {
+        if self.hex_integers() {
+            return format_hex(writer, value < 0, value.unsigned_abs().into());
+        }
         let mut buffer = itoa::Buffer::new();
         let s = buffer.format(value);
         writer.write_all(s.as_bytes())
@@ -98,6 +426,9 @@ pub trait Formatter {
     where
         W: ?Sized + io::Write,
     {
+        if self.hex_integers() {
+            return format_hex(writer, false, value.into());
+        }
         let mut buffer = itoa::Buffer::new();
         let s = buffer.format(value);
         writer.write_all(s.as_bytes())
@@ -108,6 +439,9 @@ pub trait Formatter {
     where
         W: ?Sized + io::Write,
     {
+        if self.hex_integers() {
+            return format_hex(writer, false, value.into());
+        }
         let mut buffer = itoa::Buffer::new();
         let s = buffer.format(value);
         writer.write_all(s.as_bytes())
@@ -118,6 +452,9 @@ pub trait Formatter {
     where
         W: ?Sized + io::Write,
     {
+        if self.hex_integers() {
+            return format_hex(writer, false, value.into());
+        }
         let mut buffer = itoa::Buffer::new();
         let s = buffer.format(value);
         writer.write_all(s.as_bytes())
@@ -128,6 +465,35 @@ pub trait Formatter {
     where
         W: ?Sized + io::Write,
     {
+        if self.hex_integers() {
+            return format_hex(writer, false, value.into());
+        }
+        let mut buffer = itoa::Buffer::new();
+        let s = buffer.format(value);
+        writer.write_all(s.as_bytes())
+    }
+
+    #[inline]
+    fn write_i128<W>(&mut self, writer: &mut W, value: i128) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        if self.hex_integers() {
+            return format_hex(writer, value < 0, value.unsigned_abs());
+        }
+        let mut buffer = itoa::Buffer::new();
+        let s = buffer.format(value);
+        writer.write_all(s.as_bytes())
+    }
+
+    #[inline]
+    fn write_u128<W>(&mut self, writer: &mut W, value: u128) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        if self.hex_integers() {
+            return format_hex(writer, false, value);
+        }
         let mut buffer = itoa::Buffer::new();
         let s = buffer.format(value);
         writer.write_all(s.as_bytes())
@@ -139,7 +505,7 @@ pub trait Formatter {
         W: ?Sized + io::Write,
     {
         let mut buffer = ryu::Buffer::new();
-        let s = buffer.format_finite(value);
+        let s = buffer.format(value);
         writer.write_all(s.as_bytes())
     }
 
@@ -149,16 +515,60 @@ pub trait Formatter {
         W: ?Sized + io::Write,
     {
         let mut buffer = ryu::Buffer::new();
-        let s = buffer.format_finite(value);
+        let s = buffer.format(value);
         writer.write_all(s.as_bytes())
     }
 
+    #[inline]
+    fn write_key<W>(&mut self, writer: &mut W, key: &str) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        format_escaped_str(writer, key, true)
+    }
+
     #[inline]
     fn write_str<W>(&mut self, writer: &mut W, value: &str) -> io::Result<()>
     where
         W: ?Sized + io::Write,
     {
-        writer.write_all(value.as_bytes())
+        format_escaped_str(writer, value, false)
+    }
+
+    /// How `write_bytes` encodes byte strings.
+    #[inline]
+    fn bytes_encoding(&self) -> BytesEncoding {
+        BytesEncoding::Hex
+    }
+
+    /// Whether integers are written in hex, as `0x1f`. They are read back
+    /// with `Deserializer::with_lenient_literals`.
+    #[inline]
+    fn hex_integers(&self) -> bool {
+        false
+    }
+
+    /// Whether booleans are written as `1` and `0`. They are read back with
+    /// `Deserializer::with_lenient_literals`.
+    #[inline]
+    fn numeric_bools(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn write_bytes<W>(&mut self, writer: &mut W, value: &[u8]) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.bytes_encoding().encode(writer, value)
+    }
+
+    #[inline]
+    fn write_line_continuation<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        writer.write_all(b"\\\n    ")
     }
 
     #[inline]
@@ -169,6 +579,34 @@ pub trait Formatter {
         writer.write_all(b"\n")
     }
 
+    /// Called after `begin_key` when the part of the key before its first dot
+    /// differs from the last key's.
+    #[inline]
+    fn begin_group<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        writer.write_all(b"")
+    }
+
+    /// Whether keys are padded to the width of the longest one. Entries are
+    /// then held back until the whole file has been serialized.
+    #[inline]
+    fn align_keys(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn write_key_padding<W>(&mut self, writer: &mut W, width: usize) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        for _ in 0..width {
+            writer.write_all(b" ")?;
+        }
+        Ok(())
+    }
+
     #[inline]
     fn end_key<W>(&mut self, writer: &mut W) -> io::Result<()>
     where
@@ -192,6 +630,45 @@ pub trait Formatter {
     {
         writer.write_all(b"")
     }
+
+    /// Writes a single line of a comment.
+    #[inline]
+    fn write_comment<W>(
+        &mut self,
+        writer: &mut W,
+        marker: CommentMarker,
+        line: &str,
+    ) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        writer.write_all(match marker {
+            CommentMarker::Hash => b"#",
+            CommentMarker::Bang => b"!",
+        })?;
+        if line.is_empty() {
+            return Ok(());
+        }
+        writer.write_all(b" ")?;
+        writer.write_all(line.as_bytes())
+    }
+
+    #[inline]
+    fn end_comment<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        writer.write_all(b"\n")
+    }
+
+    /// Called after the last entry of a file that has any.
+    #[inline]
+    fn end_document<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        writer.write_all(b"")
+    }
 }
 
 impl<'a, W: io::Write, F: Formatter> ser::Serializer for &'a mut Serializer<W, F> {
@@ -207,86 +684,103 @@ impl<'a, W: io::Write, F: Formatter> ser::Serializer for &'a mut Serializer<W, F
     type SerializeStructVariant = Compound<'a, W, F>;
 
     fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
-        self.formatter
-            .write_bool(&mut self.writer, v)
-            .map_err(ser::Error::custom)
+        self.write_scalar(|formatter, writer| formatter.write_bool(writer, v))
     }
 
     fn serialize_i8(self, v: i8) -> Result<Self::Ok> {
-        self.formatter
-            .write_i8(&mut self.writer, v)
-            .map_err(ser::Error::custom)
+        self.write_scalar(|formatter, writer| formatter.write_i8(writer, v))
     }
 
     fn serialize_i16(self, v: i16) -> Result<Self::Ok> {
-        self.formatter
-            .write_i16(&mut self.writer, v)
-            .map_err(ser::Error::custom)
+        self.write_scalar(|formatter, writer| formatter.write_i16(writer, v))
     }
 
     fn serialize_i32(self, v: i32) -> Result<Self::Ok> {
-        self.formatter
-            .write_i32(&mut self.writer, v)
-            .map_err(ser::Error::custom)
+        self.write_scalar(|formatter, writer| formatter.write_i32(writer, v))
     }
 
     fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
-        self.formatter
-            .write_i64(&mut self.writer, v)
-            .map_err(ser::Error::custom)
+        self.write_scalar(|formatter, writer| formatter.write_i64(writer, v))
     }
 
     fn serialize_u8(self, v: u8) -> Result<Self::Ok> {
-        self.formatter
-            .write_u8(&mut self.writer, v)
-            .map_err(ser::Error::custom)
+        self.write_scalar(|formatter, writer| formatter.write_u8(writer, v))
     }
 
     fn serialize_u16(self, v: u16) -> Result<Self::Ok> {
-        self.formatter
-            .write_u16(&mut self.writer, v)
-            .map_err(ser::Error::custom)
+        self.write_scalar(|formatter, writer| formatter.write_u16(writer, v))
     }
 
     fn serialize_u32(self, v: u32) -> Result<Self::Ok> {
-        self.formatter
-            .write_u32(&mut self.writer, v)
-            .map_err(ser::Error::custom)
+        self.write_scalar(|formatter, writer| formatter.write_u32(writer, v))
     }
 
     fn serialize_u64(self, v: u64) -> Result<Self::Ok> {
-        self.formatter
-            .write_u64(&mut self.writer, v)
-            .map_err(ser::Error::custom)
+        self.write_scalar(|formatter, writer| formatter.write_u64(writer, v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok> {
+        self.write_scalar(|formatter, writer| formatter.write_i128(writer, v))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok> {
+        self.write_scalar(|formatter, writer| formatter.write_u128(writer, v))
     }
 
     fn serialize_f32(self, v: f32) -> Result<Self::Ok> {
-        self.formatter
-            .write_f32(&mut self.writer, v)
-            .map_err(ser::Error::custom)
+        self.write_scalar(|formatter, writer| formatter.write_f32(writer, v))
     }
 
     fn serialize_f64(self, v: f64) -> Result<Self::Ok> {
-        self.formatter
-            .write_f64(&mut self.writer, v)
-            .map_err(ser::Error::custom)
+        self.write_scalar(|formatter, writer| formatter.write_f64(writer, v))
     }
 
-    fn serialize_char(self, _v: char) -> Result<Self::Ok> {
-        Err(ser::Error::custom("unsupported char"))
+    fn serialize_char(self, v: char) -> Result<Self::Ok> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
     }
 
     fn serialize_str(self, v: &str) -> Result<Self::Ok> {
+        if self.element.is_some()
+            && (v.is_empty()
+                || v.contains(self.seq_separator)
+                || v.starts_with(' ')
+                || v.ends_with(' '))
+        {
+            return Err(ser::Error::custom(format_args!(
+                "element {:?} would not read back unchanged from a delimited seq",
+                v
+            )));
+        }
+        self.begin_entry()?;
+        let mut rest = v;
+        if let Some(width) = self.line_width {
+            while let Some(i) = wrap_index(rest, width) {
+                self.formatter
+                    .write_str(&mut self.writer, &rest[..i])
+                    .map_err(Error::io)?;
+                self.formatter
+                    .write_line_continuation(&mut self.writer)
+                    .map_err(Error::io)?;
+                rest = &rest[i..];
+            }
+        }
+
         self.formatter
-            .write_str(&mut self.writer, v)
-            .map_err(ser::Error::custom)
+            .write_str(&mut self.writer, rest)
+            .map_err(Error::io)?;
+        self.end_entry()
     }
 
-    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok> {
-        Err(ser::Error::custom("unsupported bytes"))
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok> {
+        self.write_scalar(|formatter, writer| formatter.write_bytes(writer, v))
     }
 
     fn serialize_none(self) -> Result<Self::Ok> {
+        // Elements of a sequence written as a single value are never skipped,
+        // so the others keep their place.
+        if self.skip_none && self.depth > 0 && self.element.is_none() {
+            return Ok(());
+        }
         self.serialize_unit()
     }
 
@@ -298,9 +792,10 @@ impl<'a, W: io::Write, F: Formatter> ser::Serializer for &'a mut Serializer<W, F
     }
 
     fn serialize_unit(self) -> Result<Self::Ok> {
-        self.formatter
-            .write_null(&mut self.writer)
-            .map_err(ser::Error::custom)
+        match self.null_token.clone() {
+            Some(token) => self.serialize_str(&token),
+            None => self.write_scalar(|formatter, writer| formatter.write_null(writer)),
+        }
     }
 
     fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok> {
@@ -316,56 +811,97 @@ impl<'a, W: io::Write, F: Formatter> ser::Serializer for &'a mut Serializer<W, F
         self.serialize_str(variant)
     }
 
-    fn serialize_newtype_struct<T>(self, _name: &'static str, _value: &T) -> Result<Self::Ok>
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Self::Ok>
     where
         T: ?Sized + ser::Serialize,
     {
-        Err(ser::Error::custom("unsupported newtype struct"))
+        value.serialize(self)
     }
 
+    /// Writes the variant as the value of the key. A struct or map inside it
+    /// is nested under the key like the fields of a struct variant, anything
+    /// else is written as `key.0`.
     fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _value: &T,
+        variant: &'static str,
+        value: &T,
     ) -> Result<Self::Ok>
     where
         T: ?Sized + ser::Serialize,
     {
-        Err(ser::Error::custom("unsupported newtype variant"))
+        ser::Serializer::serialize_str(&mut *self, variant)?;
+        let len = self.path.len();
+        self.path.extend_from_slice(b".0");
+        self.variant = Some(len);
+        let result = value.serialize(&mut *self);
+        self.variant = None;
+        self.path.truncate(len);
+        result
     }
 
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
-        Err(ser::Error::custom("unsupported seq"))
+        if self.depth == 0 {
+            return Err(top_level_must_be_a_map());
+        }
+        if self.element.is_some() {
+            return Err(ser::Error::custom("unsupported seq in a delimited seq"));
+        }
+
+        self.variant = None;
+        if self.indexed_seqs {
+            Ok(Compound::Seq {
+                prefix: self.begin_nested(),
+                index: 0,
+                ser: self,
+            })
+        } else {
+            self.begin_entry()?;
+            self.element = Some(true);
+            Ok(Compound::Delimited { ser: self })
+        }
     }
 
-    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
-        Err(ser::Error::custom("unsupported tuple"))
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
     }
 
     fn serialize_tuple_struct(
         self,
         _name: &'static str,
-        _len: usize,
+        len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
-        Err(ser::Error::custom("unsupported tuple struct"))
+        self.serialize_seq(Some(len))
     }
 
+    /// Writes the variant as the value of the key, and its fields as
+    /// `key.0`, `key.1` and so on.
     fn serialize_tuple_variant(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        Err(ser::Error::custom("unsupported tuple variant"))
+        ser::Serializer::serialize_str(&mut *self, variant)?;
+        Ok(Compound::Seq {
+            prefix: self.begin_nested(),
+            index: 0,
+            ser: self,
+        })
     }
 
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        if self.element.is_some() {
+            return Err(ser::Error::custom("unsupported map in a delimited seq"));
+        }
+        if let Some(len) = self.variant.take() {
+            self.path.truncate(len);
+        }
         Ok(Compound::Map {
+            prefix: self.begin_nested(),
             ser: self,
-            first: true,
         })
     }
 
@@ -373,58 +909,335 @@ impl<'a, W: io::Write, F: Formatter> ser::Serializer for &'a mut Serializer<W, F
         self.serialize_map(Some(len))
     }
 
+    /// Writes the variant as the value of the key, and its fields as
+    /// `key.field`.
     fn serialize_struct_variant(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
+        variant: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        Err(ser::Error::custom("unsupported struct variant"))
+        ser::Serializer::serialize_str(&mut *self, variant)?;
+        self.serialize_map(Some(len))
     }
 }
 
-#[derive(Clone, Debug)]
-pub struct CompactFormatter;
-
-impl Formatter for CompactFormatter {}
-
-pub enum Compound<'a, W: 'a, F: 'a> {
-    Map {
-        ser: &'a mut Serializer<W, F>,
-        first: bool,
-    },
+#[derive(Clone, Debug, Default)]
+pub struct CompactFormatter {
+    bytes_encoding: BytesEncoding,
+    hex_integers: bool,
+    numeric_bools: bool,
 }
 
-impl<'a, W: io::Write, F: Formatter> ser::SerializeSeq for Compound<'a, W, F> {
-    type Ok = ();
-    type Error = error::Error;
+impl CompactFormatter {
+    #[inline]
+    pub fn new() -> Self {
+        CompactFormatter::default()
+    }
 
-    fn serialize_element<T>(&mut self, _value: &T) -> Result<Self::Ok>
-    where
-        T: ?Sized + ser::Serialize,
-    {
-        unimplemented!()
+    #[inline]
+    pub fn with_bytes_encoding(mut self, encoding: BytesEncoding) -> Self {
+        self.bytes_encoding = encoding;
+        self
     }
 
-    fn end(self) -> Result<Self::Ok> {
-        unimplemented!()
+    #[inline]
+    pub fn with_hex_integers(mut self, hex: bool) -> Self {
+        self.hex_integers = hex;
+        self
+    }
+
+    #[inline]
+    pub fn with_numeric_bools(mut self, numeric: bool) -> Self {
+        self.numeric_bools = numeric;
+        self
     }
 }
 
-impl<'a, W: io::Write, F: Formatter> ser::SerializeTuple for Compound<'a, W, F> {
-    type Ok = ();
+impl Formatter for CompactFormatter {
+    #[inline]
+    fn bytes_encoding(&self) -> BytesEncoding {
+        self.bytes_encoding
+    }
+
+    #[inline]
+    fn hex_integers(&self) -> bool {
+        self.hex_integers
+    }
+
+    #[inline]
+    fn numeric_bools(&self) -> bool {
+        self.numeric_bools
+    }
+}
+
+/// What starts a comment line.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CommentMarker {
+    #[default]
+    Hash,
+    Bang,
+}
+
+/// What is written between a key and its value.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeySeparator {
+    /// `key=value`
+    Equals,
+    /// `key = value`
+    #[default]
+    SpacedEquals,
+    /// `key: value`
+    Colon,
+}
+
+/// A `Formatter` for files meant to be read and edited by people.
+///
+/// By default, keys are padded so that the ` = ` separators line up, keys
+/// that start with different segments are set apart by blank lines, and the
+/// file ends with a newline.
+#[derive(Clone, Debug)]
+pub struct PrettyFormatter {
+    separator: KeySeparator,
+    align: bool,
+    crlf: bool,
+    trailing_newline: bool,
+    group_spacing: bool,
+    bytes_encoding: BytesEncoding,
+    hex_integers: bool,
+    numeric_bools: bool,
+}
+
+impl PrettyFormatter {
+    #[inline]
+    pub fn new() -> Self {
+        PrettyFormatter {
+            separator: KeySeparator::default(),
+            align: true,
+            crlf: false,
+            trailing_newline: true,
+            group_spacing: true,
+            bytes_encoding: BytesEncoding::default(),
+            hex_integers: false,
+            numeric_bools: false,
+        }
+    }
+
+    #[inline]
+    pub fn with_separator(mut self, separator: KeySeparator) -> Self {
+        self.separator = separator;
+        self
+    }
+
+    #[inline]
+    pub fn with_alignment(mut self, align: bool) -> Self {
+        self.align = align;
+        self
+    }
+
+    /// Ends lines with `\r\n` instead of `\n`.
+    #[inline]
+    pub fn with_crlf(mut self, crlf: bool) -> Self {
+        self.crlf = crlf;
+        self
+    }
+
+    #[inline]
+    pub fn with_trailing_newline(mut self, trailing_newline: bool) -> Self {
+        self.trailing_newline = trailing_newline;
+        self
+    }
+
+    /// Puts a blank line between keys that don't share their first segment,
+    /// such as `server.port` and `client.port`.
+    #[inline]
+    pub fn with_group_spacing(mut self, group_spacing: bool) -> Self {
+        self.group_spacing = group_spacing;
+        self
+    }
+
+    #[inline]
+    pub fn with_bytes_encoding(mut self, encoding: BytesEncoding) -> Self {
+        self.bytes_encoding = encoding;
+        self
+    }
+
+    #[inline]
+    pub fn with_hex_integers(mut self, hex: bool) -> Self {
+        self.hex_integers = hex;
+        self
+    }
+
+    #[inline]
+    pub fn with_numeric_bools(mut self, numeric: bool) -> Self {
+        self.numeric_bools = numeric;
+        self
+    }
+
+    fn line_ending(&self) -> &'static [u8] {
+        if self.crlf {
+            b"\r\n"
+        } else {
+            b"\n"
+        }
+    }
+}
+
+impl Default for PrettyFormatter {
+    fn default() -> Self {
+        PrettyFormatter::new()
+    }
+}
+
+impl Formatter for PrettyFormatter {
+    #[inline]
+    fn bytes_encoding(&self) -> BytesEncoding {
+        self.bytes_encoding
+    }
+
+    #[inline]
+    fn hex_integers(&self) -> bool {
+        self.hex_integers
+    }
+
+    #[inline]
+    fn numeric_bools(&self) -> bool {
+        self.numeric_bools
+    }
+
+    #[inline]
+    fn write_line_continuation<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        writer.write_all(b"\\")?;
+        writer.write_all(self.line_ending())?;
+        writer.write_all(b"    ")
+    }
+
+    #[inline]
+    fn begin_key<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        writer.write_all(self.line_ending())
+    }
+
+    #[inline]
+    fn begin_group<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        if self.group_spacing {
+            writer.write_all(self.line_ending())?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn align_keys(&self) -> bool {
+        self.align
+    }
+
+    #[inline]
+    fn end_comment<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        writer.write_all(self.line_ending())
+    }
+
+    #[inline]
+    fn end_key<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        writer.write_all(match self.separator {
+            KeySeparator::Equals => b"=",
+            KeySeparator::SpacedEquals => b" = ",
+            KeySeparator::Colon => b": ",
+        })
+    }
+
+    #[inline]
+    fn end_document<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        if self.trailing_newline {
+            writer.write_all(self.line_ending())?;
+        }
+        Ok(())
+    }
+}
+
+pub enum Compound<'a, W: 'a, F: 'a> {
+    Map {
+        ser: &'a mut Serializer<W, F>,
+        /// Length of the path of enclosing keys.
+        prefix: usize,
+    },
+    /// A sequence written one element per indexed key.
+    Seq {
+        ser: &'a mut Serializer<W, F>,
+        prefix: usize,
+        index: usize,
+    },
+    /// A sequence written as a single value.
+    Delimited { ser: &'a mut Serializer<W, F> },
+}
+
+impl<'a, W: io::Write, F: Formatter> ser::SerializeSeq for Compound<'a, W, F> {
+    type Ok = ();
     type Error = error::Error;
 
-    fn serialize_element<T>(&mut self, _value: &T) -> Result<Self::Ok>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<Self::Ok>
     where
         T: ?Sized + ser::Serialize,
     {
-        unimplemented!()
+        match self {
+            Compound::Seq { ser, prefix, index } => {
+                ser.path.truncate(*prefix);
+                let mut buffer = itoa::Buffer::new();
+                ser.path.extend_from_slice(buffer.format(*index).as_bytes());
+                *index += 1;
+                value.serialize(&mut **ser)
+            }
+            Compound::Delimited { ser } => value.serialize(&mut **ser),
+            Compound::Map { .. } => unreachable!(),
+        }
     }
 
     fn end(self) -> Result<Self::Ok> {
-        unimplemented!()
+        match self {
+            Compound::Seq { ser, prefix, .. } => {
+                ser.depth -= 1;
+                ser.path.truncate(prefix - 1);
+                Ok(())
+            }
+            Compound::Delimited { ser } => {
+                ser.element = None;
+                ser.end_entry()
+            }
+            Compound::Map { .. } => unreachable!(),
+        }
+    }
+}
+
+impl<'a, W: io::Write, F: Formatter> ser::SerializeTuple for Compound<'a, W, F> {
+    type Ok = ();
+    type Error = error::Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<Self::Ok>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        ser::SerializeSeq::end(self)
     }
 }
 
@@ -432,15 +1245,15 @@ impl<'a, W: io::Write, F: Formatter> ser::SerializeTupleStruct for Compound<'a,
     type Ok = ();
     type Error = error::Error;
 
-    fn serialize_field<T>(&mut self, _value: &T) -> Result<Self::Ok>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<Self::Ok>
     where
         T: ?Sized + ser::Serialize,
     {
-        unimplemented!()
+        ser::SerializeSeq::serialize_element(self, value)
     }
 
     fn end(self) -> Result<Self::Ok> {
-        unimplemented!()
+        ser::SerializeSeq::end(self)
     }
 }
 
@@ -448,15 +1261,15 @@ impl<'a, W: io::Write, F: Formatter> ser::SerializeTupleVariant for Compound<'a,
     type Ok = ();
     type Error = error::Error;
 
-    fn serialize_field<T>(&mut self, _value: &T) -> Result<Self::Ok>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<Self::Ok>
     where
         T: ?Sized + ser::Serialize,
     {
-        unimplemented!()
+        ser::SerializeSeq::serialize_element(self, value)
     }
 
     fn end(self) -> Result<Self::Ok> {
-        unimplemented!()
+        ser::SerializeSeq::end(self)
     }
 }
 
@@ -469,20 +1282,11 @@ impl<'a, W: io::Write, F: Formatter> ser::SerializeMap for Compound<'a, W, F> {
         T: ?Sized + ser::Serialize,
     {
         match self {
-            Compound::Map { ser, first } => {
-                if !*first {
-                    ser.formatter
-                        .begin_key(&mut ser.writer)
-                        .map_err(ser::Error::custom)?;
-                } else {
-                    *first = false
-                }
-
-                key.serialize(&mut **ser)?;
-                ser.formatter
-                    .end_key(&mut ser.writer)
-                    .map_err(ser::Error::custom)
+            Compound::Map { ser, prefix } => {
+                ser.path.truncate(*prefix);
+                key.serialize(MapKeySerializer { ser: &mut **ser })
             }
+            _ => unreachable!(),
         }
     }
 
@@ -491,21 +1295,23 @@ impl<'a, W: io::Write, F: Formatter> ser::SerializeMap for Compound<'a, W, F> {
         T: ?Sized + ser::Serialize,
     {
         match self {
-            Compound::Map { ser, .. } => {
-                ser.formatter
-                    .begin_value(&mut ser.writer)
-                    .map_err(ser::Error::custom)?;
-
-                value.serialize(&mut **ser)?;
-                ser.formatter
-                    .end_value(&mut ser.writer)
-                    .map_err(ser::Error::custom)
-            }
+            Compound::Map { ser, .. } => value.serialize(&mut **ser),
+            _ => unreachable!(),
         }
     }
 
     fn end(self) -> Result<Self::Ok> {
-        Ok(())
+        match self {
+            Compound::Map { ser, prefix } => {
+                ser.depth -= 1;
+                ser.path.truncate(prefix.saturating_sub(1));
+                if ser.depth == 0 {
+                    ser.end_document()?;
+                }
+                Ok(())
+            }
+            _ => unreachable!(),
+        }
     }
 }
 
@@ -519,11 +1325,12 @@ impl<'a, W: io::Write, F: Formatter> ser::SerializeStruct for Compound<'a, W, F>
     {
         match self {
             Compound::Map { .. } => ser::SerializeMap::serialize_entry(self, key, value),
+            _ => unreachable!(),
         }
     }
 
     fn end(self) -> Result<Self::Ok> {
-        Ok(())
+        ser::SerializeMap::end(self)
     }
 }
 
@@ -531,15 +1338,355 @@ impl<'a, W: io::Write, F: Formatter> ser::SerializeStructVariant for Compound<'a
     type Ok = ();
     type Error = error::Error;
 
-    fn serialize_field<T>(&mut self, _key: &'static str, _value: &T) -> Result<Self::Ok>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<Self::Ok>
     where
         T: ?Sized + ser::Serialize,
     {
-        unimplemented!()
+        ser::SerializeStruct::serialize_field(self, key, value)
     }
 
     fn end(self) -> Result<Self::Ok> {
-        unimplemented!()
+        ser::SerializeMap::end(self)
+    }
+}
+
+fn format_hex<W>(writer: &mut W, negative: bool, mut value: u128) -> io::Result<()>
+where
+    W: ?Sized + io::Write,
+{
+    static HEX_DIGITS: [u8; 16] = *b"0123456789abcdef";
+
+    // A sign, the prefix and up to 32 digits.
+    let mut buf = [0; 35];
+    let mut i = buf.len();
+    loop {
+        i -= 1;
+        buf[i] = HEX_DIGITS[(value & 0xF) as usize];
+        value >>= 4;
+        if value == 0 {
+            break;
+        }
+    }
+    i -= 2;
+    buf[i..i + 2].copy_from_slice(b"0x");
+    if negative {
+        i -= 1;
+        buf[i] = b'-';
+    }
+    writer.write_all(&buf[i..])
+}
+
+// Escapes `value` so that it reads back unchanged as a key or a value. Keys
+// additionally escape separators, whitespace and a leading comment marker.
+pub(crate) fn format_escaped_str<W>(writer: &mut W, value: &str, key: bool) -> io::Result<()>
+where
+    W: ?Sized + io::Write,
+{
+    let bytes = value.as_bytes();
+    let mut start = 0;
+    for (i, &byte) in bytes.iter().enumerate() {
+        let escape: &[u8] = match byte {
+            b'\\' => b"\\\\",
+            b'\t' => b"\\t",
+            b'\n' => b"\\n",
+            b'\r' => b"\\r",
+            b'\x0c' => b"\\f",
+            b'=' if key => b"\\=",
+            b':' if key => b"\\:",
+            b' ' if key || i == 0 => b"\\ ",
+            b'#' if key && i == 0 => b"\\#",
+            b'!' if key && i == 0 => b"\\!",
+            0x00..=0x1F => b"",
+            _ => continue,
+        };
+
+        if start < i {
+            writer.write_all(&bytes[start..i])?;
+        }
+        if escape.is_empty() {
+            write_unicode_escape(writer, byte as u16)?;
+        } else {
+            writer.write_all(escape)?;
+        }
+        start = i + 1;
+    }
+
+    if start < bytes.len() {
+        writer.write_all(&bytes[start..])?;
+    }
+    Ok(())
+}
+
+// Returns where to break `value` so that its first line is at most `width`
+// bytes long. Breaks only go right after whitespace, as leading whitespace of
+// a continuation line is dropped when it is read back.
+fn wrap_index(value: &str, width: usize) -> Option<usize> {
+    if value.len() <= width {
+        return None;
+    }
+
+    let mut index = None;
+    let mut prev = None;
+    for (i, ch) in value.char_indices() {
+        if prev == Some(' ') && ch != ' ' {
+            if i > width && index.is_some() {
+                break;
+            }
+            index = Some(i);
+        }
+        prev = Some(ch);
+    }
+    index
+}
+
+// A file is a list of keys, so only maps and structs can be written as one.
+fn top_level_must_be_a_map() -> error::Error {
+    ser::Error::custom("top-level must be a struct or map")
+}
+
+enum KeyOrder {
+    Serialized,
+    Sorted,
+    Custom(Vec<Vec<u8>>),
+}
+
+// Formats a dotted key the way the serializer writes it.
+fn format_key(key: &str) -> Vec<u8> {
+    let mut formatted = Vec::new();
+    for (i, segment) in key.split('.').enumerate() {
+        if i > 0 {
+            formatted.push(b'.');
+        }
+        // Writing to a `Vec` cannot fail.
+        let _ = format_escaped_str(&mut formatted, segment, true);
+    }
+    formatted
+}
+
+fn compare_keys(a: &[u8], b: &[u8]) -> Ordering {
+    let is_number = |segment: &[u8]| !segment.is_empty() && segment.iter().all(u8::is_ascii_digit);
+    let mut a = a.split(|&b| b == b'.');
+    let mut b = b.split(|&b| b == b'.');
+    loop {
+        let ordering = match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            // Leading zeros aren't written for indices.
+            (Some(x), Some(y)) if is_number(x) && is_number(y) => {
+                x.len().cmp(&y.len()).then_with(|| x.cmp(y))
+            }
+            (Some(x), Some(y)) => x.cmp(y),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+struct MapKeySerializer<'a, W: 'a, F: 'a> {
+    ser: &'a mut Serializer<W, F>,
+}
+
+// Keys are read back as strings, so they ignore the formatter's options for
+// integers and booleans.
+impl<'a, W, F> MapKeySerializer<'a, W, F> {
+    fn write_integer<I: itoa::Integer>(self, v: I) -> Result<()> {
+        let mut buffer = itoa::Buffer::new();
+        self.ser.path.extend_from_slice(buffer.format(v).as_bytes());
+        Ok(())
+    }
+}
+
+fn key_must_be_a_string() -> error::Error {
+    ser::Error::custom("key must be a string")
+}
+
+impl<'a, W: io::Write, F: Formatter> ser::Serializer for MapKeySerializer<'a, W, F> {
+    type Ok = ();
+    type Error = error::Error;
+
+    type SerializeSeq = Impossible<(), error::Error>;
+    type SerializeTuple = Impossible<(), error::Error>;
+    type SerializeTupleStruct = Impossible<(), error::Error>;
+    type SerializeTupleVariant = Impossible<(), error::Error>;
+    type SerializeMap = Impossible<(), error::Error>;
+    type SerializeStruct = Impossible<(), error::Error>;
+    type SerializeStructVariant = Impossible<(), error::Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
+        let s = if v { "true" } else { "false" };
+        self.ser.path.extend_from_slice(s.as_bytes());
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok> {
+        self.write_integer(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok> {
+        self.write_integer(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok> {
+        self.write_integer(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
+        self.write_integer(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok> {
+        self.write_integer(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok> {
+        self.write_integer(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok> {
+        self.write_integer(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok> {
+        self.write_integer(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok> {
+        self.write_integer(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok> {
+        self.write_integer(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok> {
+        self.ser
+            .formatter
+            .write_f32(&mut self.ser.path, v)
+            .map_err(Error::io)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok> {
+        self.ser
+            .formatter
+            .write_f64(&mut self.ser.path, v)
+            .map_err(Error::io)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok> {
+        // The key of a nested map is written after its parent's, so these
+        // would read back as more levels of nesting.
+        if !self.ser.path.is_empty() && v.contains(['.', '[', ']']) {
+            return Err(ser::Error::custom(format_args!(
+                "nested key {:?} contains `.`, `[` or `]`",
+                v
+            )));
+        }
+        self.ser
+            .formatter
+            .write_key(&mut self.ser.path, v)
+            .map_err(Error::io)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> Result<Self::Ok>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Self::Ok>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(key_must_be_a_string())
     }
 }
 
@@ -553,6 +1700,17 @@ where
     value.serialize(&mut ser)
 }
 
+/// Serializes `value` with a default `PrettyFormatter`.
+#[inline]
+pub fn to_writer_pretty<W, T>(writer: W, value: &T) -> Result<()>
+where
+    W: io::Write,
+    T: ?Sized + ser::Serialize,
+{
+    let mut ser = Serializer::with_formatter(writer, PrettyFormatter::new());
+    value.serialize(&mut ser)
+}
+
 #[inline]
 pub fn to_vec<T>(value: &T) -> Result<Vec<u8>>
 where
@@ -569,6 +1727,24 @@ where
     T: ?Sized + ser::Serialize,
 {
     let vec = to_vec(value)?;
-    let string = unsafe { String::from_utf8_unchecked(vec) };
-    Ok(string)
+    String::from_utf8(vec).map_err(ser::Error::custom)
+}
+
+#[inline]
+pub fn to_vec_pretty<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: ?Sized + ser::Serialize,
+{
+    let mut writer = Vec::with_capacity(128);
+    to_writer_pretty(&mut writer, value)?;
+    Ok(writer)
+}
+
+#[inline]
+pub fn to_string_pretty<T>(value: &T) -> Result<String>
+where
+    T: ?Sized + ser::Serialize,
+{
+    let vec = to_vec_pretty(value)?;
+    String::from_utf8(vec).map_err(ser::Error::custom)
 }
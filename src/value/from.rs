@@ -0,0 +1,120 @@
+use alloc::borrow::Cow;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use super::Value;
+use crate::map::Map;
+
+macro_rules! from_integer {
+    ($($ty:ty)*) => {
+        $(
+            impl From<$ty> for Value {
+                fn from(n: $ty) -> Self {
+                    Value::Integer(n.into())
+                }
+            }
+        )*
+    };
+}
+
+from_integer!(i8 i16 i32 i64 u8 u16 u32);
+
+impl From<u64> for Value {
+    /// Numbers too large for an `i64` are kept as strings.
+    fn from(n: u64) -> Self {
+        match i64::try_from(n) {
+            Ok(n) => Value::Integer(n),
+            Err(_) => Value::String(n.to_string()),
+        }
+    }
+}
+
+impl From<i128> for Value {
+    /// Numbers too large for an `i64` are kept as strings.
+    fn from(n: i128) -> Self {
+        match i64::try_from(n) {
+            Ok(n) => Value::Integer(n),
+            Err(_) => Value::String(n.to_string()),
+        }
+    }
+}
+
+impl From<u128> for Value {
+    /// Numbers too large for an `i64` are kept as strings.
+    fn from(n: u128) -> Self {
+        match i64::try_from(n) {
+            Ok(n) => Value::Integer(n),
+            Err(_) => Value::String(n.to_string()),
+        }
+    }
+}
+
+impl From<f32> for Value {
+    fn from(n: f32) -> Self {
+        Value::Float(n.into())
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::Float(n)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.into())
+    }
+}
+
+impl<'a> From<Cow<'a, str>> for Value {
+    fn from(s: Cow<'a, str>) -> Self {
+        Value::String(s.into_owned())
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(seq: Vec<T>) -> Self {
+        Value::Seq(seq.into_iter().map(Into::into).collect())
+    }
+}
+
+impl From<Map<String, Value>> for Value {
+    fn from(map: Map<String, Value>) -> Self {
+        Value::Map(map)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
+}
+
+impl From<()> for Value {
+    fn from((): ()) -> Self {
+        Value::Null
+    }
+}
+
+impl<K: Into<String>, V: Into<Value>> FromIterator<(K, V)> for Value {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Value::Map(
+            iter.into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        )
+    }
+}
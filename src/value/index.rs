@@ -0,0 +1,71 @@
+use alloc::string::String;
+use core::ops;
+
+use super::Value;
+use crate::map::Map;
+
+impl ops::Index<usize> for Value {
+    type Output = Value;
+
+    /// Returns the element at `index`, or `Value::Null` if this isn't a
+    /// sequence or is too short.
+    fn index(&self, index: usize) -> &Value {
+        static NULL: Value = Value::Null;
+        self.as_seq()
+            .and_then(|seq| seq.get(index))
+            .unwrap_or(&NULL)
+    }
+}
+
+impl ops::IndexMut<usize> for Value {
+    /// Returns the element at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this isn't a sequence or `index` is out of bounds.
+    fn index_mut(&mut self, index: usize) -> &mut Value {
+        match self {
+            Value::Seq(seq) => {
+                let len = seq.len();
+                seq.get_mut(index).unwrap_or_else(|| {
+                    panic!("cannot access index {} of a Seq of length {}", index, len)
+                })
+            }
+            _ => panic!("cannot index into a non-seq Value with {}", index),
+        }
+    }
+}
+
+impl ops::Index<&str> for Value {
+    type Output = Value;
+
+    /// Returns the value of `key`, or `Value::Null` if this isn't a map or
+    /// doesn't have the key.
+    fn index(&self, key: &str) -> &Value {
+        static NULL: Value = Value::Null;
+        self.get(key).unwrap_or(&NULL)
+    }
+}
+
+impl ops::IndexMut<&str> for Value {
+    /// Returns the value of `key`, inserting `Value::Null` if the key is
+    /// missing. A `Value::Null` is turned into an empty map first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is neither a map nor `Value::Null`.
+    fn index_mut(&mut self, key: &str) -> &mut Value {
+        if let Value::Null = self {
+            *self = Value::Map(Map::new());
+        }
+        match self {
+            Value::Map(map) => {
+                if !map.contains_key(key) {
+                    map.insert(String::from(key), Value::Null);
+                }
+                map.get_mut(key).unwrap()
+            }
+            _ => panic!("cannot index into a non-map Value with {:?}", key),
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use serde::de::{DeserializeOwned, Unexpected};
+use serde::Serialize;
+
+use crate::error::Error;
+use crate::map::Map;
+
+pub use self::ser::Serializer;
+
+mod de;
+mod from;
+mod index;
+mod ser;
+
+/// Any value that can be held in a `.properties` file.
+///
+/// Values read from a file are strings, unless the deserializer infers types,
+/// and the keys of a file make up a `Map`. Sequences, byte strings and tuples
+/// become a `Seq`, and an enum variant with data a `Map` of one entry.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Value {
+    /// An empty value, or a `None`.
+    #[default]
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Seq(Vec<Value>),
+    Map(Map<String, Value>),
+}
+
+impl Value {
+    /// Looks up a key if this is a map.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.as_map()?.get(key)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
+        self.as_map_mut()?.get_mut(key)
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Value::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Value::Integer(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::Integer(n) => Some(n as f64),
+            Value::Float(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_seq(&self) -> Option<&Vec<Value>> {
+        match self {
+            Value::Seq(seq) => Some(seq),
+            _ => None,
+        }
+    }
+
+    pub fn as_seq_mut(&mut self) -> Option<&mut Vec<Value>> {
+        match self {
+            Value::Seq(seq) => Some(seq),
+            _ => None,
+        }
+    }
+
+    pub fn as_map(&self) -> Option<&Map<String, Value>> {
+        match self {
+            Value::Map(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    pub fn as_map_mut(&mut self) -> Option<&mut Map<String, Value>> {
+        match self {
+            Value::Map(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    fn unexpected(&self) -> Unexpected<'_> {
+        match *self {
+            Value::Null => Unexpected::Unit,
+            Value::Bool(b) => Unexpected::Bool(b),
+            Value::Integer(n) => Unexpected::Signed(n),
+            Value::Float(n) => Unexpected::Float(n),
+            Value::String(ref s) => Unexpected::Str(s),
+            Value::Seq(_) => Unexpected::Seq,
+            Value::Map(_) => Unexpected::Map,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    /// Formats a scalar the way it's written as a value in a file, and a
+    /// sequence as its elements joined by `,`. Maps are formatted as a whole
+    /// file.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Null => Ok(()),
+            Value::Bool(b) => fmt::Display::fmt(b, f),
+            Value::Integer(n) => fmt::Display::fmt(n, f),
+            Value::Float(n) => fmt::Display::fmt(n, f),
+            Value::String(s) => f.write_str(s),
+            Value::Seq(seq) => {
+                for (i, value) in seq.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    fmt::Display::fmt(value, f)?;
+                }
+                Ok(())
+            }
+            Value::Map(map) => {
+                let s = crate::ser::to_string(map).map_err(|_| fmt::Error)?;
+                f.write_str(&s)
+            }
+        }
+    }
+}
+
+/// Converts a `T` into a `Value`.
+pub fn to_value<T>(value: T) -> Result<Value, Error>
+where
+    T: Serialize,
+{
+    value.serialize(Serializer)
+}
+
+/// Interprets a `Value` as a `T`. String values are parsed as needed, as they
+/// would be when reading a file.
+pub fn from_value<T>(value: Value) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+{
+    T::deserialize(value)
+}
@@ -1,4 +1,10 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
+use serde_prop::{
+    BytesEncoding, Category, CommentMarker, CompactFormatter, Deserializer, Dialect, Document,
+    DuplicateKey, Encoding, KeySeparator, Merge, PrettyFormatter, Serializer,
+};
 
 #[derive(Deserialize, Serialize, Debug)]
 struct Value {
@@ -62,3 +68,1237 @@ fn to_string() {
         "unused_0=unused 0\nunused1=\nunused2=2\nunused3=3\nunused4=4.4\nunused5=u0"
     );
 }
+
+#[test]
+fn from_str_java() {
+    let prop = "\
+# comment
+  ! comment
+key1 = value\\tone
+key2:value2
+key3 value 3
+key\\ 4=\\u00e9\\=\\:\\ud83d\\ude00
+key5 =  = x 
+continued = first \\
+    second
+empty
+";
+
+    let mut de = Deserializer::from_str(prop).with_dialect(Dialect::Java);
+    let map: BTreeMap<String, Option<String>> = Deserialize::deserialize(&mut de).unwrap();
+    de.end().unwrap();
+
+    assert_eq!(map["key1"].as_deref(), Some("value\tone"));
+    assert_eq!(map["key2"].as_deref(), Some("value2"));
+    assert_eq!(map["key3"].as_deref(), Some("value 3"));
+    assert_eq!(map["key 4"].as_deref(), Some("é=:😀"));
+    assert_eq!(map["key5"].as_deref(), Some("= x "));
+    assert_eq!(map["continued"].as_deref(), Some("first second"));
+    assert_eq!(map["empty"], None);
+    assert_eq!(map.len(), 7);
+}
+
+#[test]
+fn from_str_java_malformed_unicode() {
+    let mut de = Deserializer::from_str("key=\\u00g0").with_dialect(Dialect::Java);
+    let err = BTreeMap::<String, String>::deserialize(&mut de).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "malformed \\uxxxx encoding at line 1 column 9"
+    );
+}
+
+#[test]
+fn from_str_continuation() {
+    let prop = "desc=first part \\\n    second part\npath=C:\\\\\nnext=1\n";
+
+    let mut de = Deserializer::from_str(prop).with_dialect(Dialect::Lenient);
+    let map: BTreeMap<String, String> = Deserialize::deserialize(&mut de).unwrap();
+    assert_eq!(map["desc"], "first part second part");
+    assert_eq!(map["path"], "C:\\\\");
+    assert_eq!(map["next"], "1");
+}
+
+#[test]
+fn to_writer_line_width() {
+    let mut map = BTreeMap::new();
+    map.insert("desc", "the quick brown fox jumps over the lazy dog");
+
+    let mut ser = Serializer::new(Vec::new()).with_line_width(16);
+    map.serialize(&mut ser).unwrap();
+    let s = String::from_utf8(ser.into_inner()).unwrap();
+    assert_eq!(
+        s,
+        "desc=the quick brown \\\n    fox jumps over \\\n    the lazy dog"
+    );
+
+    let map: BTreeMap<String, String> = serde_prop::from_str(&s).unwrap();
+    assert_eq!(map["desc"], "the quick brown fox jumps over the lazy dog");
+}
+
+#[test]
+fn to_string_escaped() {
+    let mut map = BTreeMap::new();
+    map.insert("a b", " leading space");
+    map.insert("a=b:c", "line\nbreak\r\n");
+    map.insert("#comment", "trailing\\");
+    map.insert("!bang", "=:#! kept");
+    map.insert("tab\tkey", "\u{1}control");
+    map.insert("ünïcödé", "https://example.com");
+
+    let s = serde_prop::to_string(&map).unwrap();
+    assert_eq!(
+        s,
+        "\\!bang==:#! kept\n\\#comment=trailing\\\\\na\\ b=\\ leading space\n\
+         a\\=b\\:c=line\\nbreak\\r\\n\ntab\\tkey=\\u0001control\n\
+         ünïcödé=https://example.com"
+    );
+
+    let de: BTreeMap<String, String> = serde_prop::from_str(&s).unwrap();
+    assert_eq!(de.len(), map.len());
+    for (k, v) in &map {
+        assert_eq!(de[*k], *v);
+    }
+
+    let map = BTreeMap::from([
+        ("a=b", "x"),
+        ("k", "line\nbreak"),
+        ("p", "C:\\dir\\"),
+        ("lead", " sp"),
+    ]);
+    let s = serde_prop::to_string(&map).unwrap();
+    let de: BTreeMap<String, String> = serde_prop::from_str(&s).unwrap();
+    assert_eq!(de.len(), map.len());
+    for (k, v) in &map {
+        assert_eq!(de[*k], *v);
+    }
+    let chars = BTreeMap::from([("c", ' ')]);
+    let s = serde_prop::to_string(&chars).unwrap();
+    assert_eq!(
+        serde_prop::from_str::<BTreeMap<&str, char>>(&s).unwrap(),
+        chars
+    );
+}
+
+#[test]
+fn to_string_non_finite() {
+    let mut map = BTreeMap::new();
+    map.insert("inf".to_owned(), f64::INFINITY);
+    map.insert("neg".to_owned(), f64::NEG_INFINITY);
+
+    let s = serde_prop::to_string(&map).unwrap();
+    assert_eq!(s, "inf=inf\nneg=-inf");
+    assert_eq!(
+        serde_prop::from_str::<BTreeMap<String, f64>>(&s).unwrap(),
+        map
+    );
+}
+
+#[test]
+fn to_string_empty() {
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    struct O {
+        a: String,
+        b: String,
+    }
+
+    let o = O {
+        a: "x".to_owned(),
+        b: String::new(),
+    };
+    let s = serde_prop::to_string(&o).unwrap();
+    assert_eq!(s, "a=x\nb=");
+    assert_eq!(serde_prop::from_str::<O>(&s).unwrap(), o);
+
+    let mut de = Deserializer::from_str(&s).with_dialect(Dialect::Lenient);
+    assert_eq!(O::deserialize(&mut de).unwrap(), o);
+}
+
+#[test]
+fn from_slice_latin1() {
+    let mut de =
+        Deserializer::from_slice(b"name=caf\xe9\nchar=\\u4e2d").with_encoding(Encoding::Latin1);
+    let map: BTreeMap<String, String> = Deserialize::deserialize(&mut de).unwrap();
+    assert_eq!(map["name"], "café");
+    assert_eq!(map["char"], "中");
+
+    let err = serde_prop::from_slice::<BTreeMap<String, String>>(b"name=caf\xe9").unwrap_err();
+    assert_eq!(err.to_string(), "invalid UTF-8 at line 1 column 9");
+    assert!(err.is_syntax());
+}
+
+#[test]
+fn to_writer_latin1() {
+    let mut map = BTreeMap::new();
+    map.insert("näme", "café 中😀");
+
+    let mut ser = Serializer::new(Vec::new()).with_encoding(Encoding::Latin1);
+    map.serialize(&mut ser).unwrap();
+    let bytes = ser.into_inner();
+    assert_eq!(bytes, b"n\xe4me=caf\xe9 \\u4E2D\\uD83D\\uDE00");
+
+    let mut de = Deserializer::from_slice(&bytes).with_encoding(Encoding::Latin1);
+    let de: BTreeMap<String, String> = Deserialize::deserialize(&mut de).unwrap();
+    assert_eq!(de["näme"], "café 中😀");
+}
+
+#[test]
+fn from_str_lenient_keys() {
+    let prop = "  my key = 1\n# a=b\nro.prop:2\ndesc=hello\tworld\n";
+    let mut de = Deserializer::from_str(prop).with_dialect(Dialect::Lenient);
+    let map: BTreeMap<String, String> = Deserialize::deserialize(&mut de).unwrap();
+    assert_eq!(map["my key"], "1");
+    assert_eq!(map["ro.prop"], "2");
+    assert_eq!(map["desc"], "hello\tworld");
+    assert_eq!(map.len(), 3);
+
+    let mut de = Deserializer::from_str("foo\nbar=1").with_dialect(Dialect::Lenient);
+    let err = BTreeMap::<String, String>::deserialize(&mut de).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "expected `=` or `:` after key at line 1 column 3"
+    );
+}
+
+#[test]
+fn error_position() {
+    let prop = "unused_0=a\nunused1=\nunused2=abc\n";
+    let err = serde_prop::from_str::<Value>(prop).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "unused2: invalid value: string \"abc\", expected signed integer at line 3 column 1"
+    );
+    assert_eq!(err.classify(), Category::Data);
+    assert_eq!(err.key(), Some("unused2"));
+    assert_eq!(
+        format!("{:?}", err),
+        "Error(\"invalid value: string \\\"abc\\\", expected signed integer\", key: \"unused2\", line: 3, column: 1)"
+    );
+    assert_eq!((err.line(), err.column(), err.offset()), (3, 1, 20));
+
+    let err = serde_prop::from_str::<Value>("unused_0=\\u00").unwrap_err();
+    assert_eq!(err.classify(), Category::Eof);
+    assert_eq!((err.line(), err.column()), (1, 13));
+}
+
+#[test]
+fn error_key() {
+    #[derive(Deserialize, Debug)]
+    struct Module {
+        #[allow(dead_code)]
+        #[serde(rename = "versionCode")]
+        version_code: i32,
+        #[allow(dead_code)]
+        mode: Enum,
+    }
+
+    let prop = "id=test\nname=Test\nversion=1.0\nversionCode=abc\n";
+    let err = serde_prop::from_str::<Module>(prop).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "versionCode: invalid value: string \"abc\", expected signed integer at line 4 column 1"
+    );
+
+    let prop = "versionCode=1\nmode=u9\n";
+    let err = serde_prop::from_str::<Module>(prop).unwrap_err();
+    assert_eq!(err.key(), Some("mode"));
+    assert_eq!(
+        err.to_string(),
+        "mode: unknown variant `u9`, expected `u0` or `u1` at line 2 column 1"
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn from_reader() {
+    let prop = b"id=test\nname=caf\xe9\n";
+
+    let map: BTreeMap<String, String> = serde_prop::from_reader(&prop[..7]).unwrap();
+    assert_eq!(map["id"], "test");
+
+    let mut de = Deserializer::from_reader(&prop[..]).with_encoding(Encoding::Latin1);
+    let map: BTreeMap<String, String> = Deserialize::deserialize(&mut de).unwrap();
+    de.end().unwrap();
+    assert_eq!(map["name"], "café");
+
+    let err = serde_prop::from_reader::<_, BTreeMap<String, i32>>(&b"a=1\nb=x"[..]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "b: invalid value: string \"x\", expected signed integer at line 2 column 1"
+    );
+}
+
+#[test]
+fn from_str_borrowed() {
+    use std::borrow::Cow;
+
+    #[derive(Deserialize)]
+    struct Borrowed<'a> {
+        id: &'a str,
+        #[serde(borrow)]
+        name: Cow<'a, str>,
+    }
+
+    let prop = "id=test\nname = Test Module\n";
+    let value: Borrowed = serde_prop::from_str(prop).unwrap();
+    assert_eq!(value.id, "test");
+    assert!(matches!(value.name, Cow::Borrowed("Test Module")));
+
+    let prop = "id=test\nname=Test\\tModule\n";
+    let value: Borrowed = serde_prop::from_str(prop).unwrap();
+    assert!(matches!(value.name, Cow::Owned(ref name) if name == "Test\tModule"));
+
+    let mut de = Deserializer::from_str("id = te\\\n  st\nname=x").with_dialect(Dialect::Lenient);
+    assert!(Borrowed::deserialize(&mut de).is_err());
+}
+
+#[test]
+fn from_str_any() {
+    use std::collections::HashMap;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(untagged)]
+    enum Version {
+        Code(i32),
+        Name(String),
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Module {
+        id: String,
+        version: Version,
+        #[serde(flatten)]
+        extra: BTreeMap<String, String>,
+    }
+
+    let prop = "id=test\nversion=v1.0\nauthor=me\n";
+    let module: Module = serde_prop::from_str(prop).unwrap();
+    assert_eq!(module.version, Version::Name("v1.0".to_owned()));
+    assert_eq!(module.extra["author"], "me");
+
+    let prop = "id=test\nversion=1\n";
+    let mut de = Deserializer::from_str(prop).with_type_inference(true);
+    let module = Module::deserialize(&mut de).unwrap();
+    assert_eq!(module.version, Version::Code(1));
+
+    let prop = "a=1\nb=-2\nc=1.5\nd=true\ne=NaN\nf=\n";
+    let mut de = Deserializer::from_str(prop).with_type_inference(true);
+    let map: HashMap<String, serde_json::Value> = Deserialize::deserialize(&mut de).unwrap();
+    assert_eq!(
+        serde_json::to_value(map).unwrap(),
+        serde_json::json!({"a": 1, "b": -2, "c": 1.5, "d": true, "e": "NaN", "f": null})
+    );
+}
+
+#[test]
+fn document_lossless() {
+    let prop = "# Module\r\n\r\nid=test\n  name : Test Module  \n! note\nversion   v1.0\ndescription=first \\\n    second\nempty=\nlast";
+    let doc: Document = prop.parse().unwrap();
+    assert_eq!(doc.to_string(), prop);
+    assert_eq!(
+        doc.iter().collect::<Vec<_>>(),
+        [
+            ("id", "test"),
+            ("name", "Test Module  "),
+            ("version", "v1.0"),
+            ("description", "first second"),
+            ("empty", ""),
+            ("last", ""),
+        ]
+    );
+
+    let prop = "id=a\\b\ndescription=first \\\n  second\n";
+    let doc = Document::parse(prop, Dialect::Lenient).unwrap();
+    assert_eq!(doc.to_string(), prop);
+    assert_eq!(
+        doc.iter().collect::<Vec<_>>(),
+        [("id", "a\\b"), ("description", "first second")]
+    );
+
+    let prop = "import /vendor/build.prop\ndesc=hello\tworld\r\nlast";
+    let mut doc = Document::parse(prop, Dialect::Lenient).unwrap();
+    assert_eq!(doc.to_string(), prop);
+    assert_eq!(doc.iter().collect::<Vec<_>>(), [("desc", "hello\tworld")]);
+    doc.set("desc", "x").unwrap();
+    assert_eq!(doc.to_string(), "import /vendor/build.prop\ndesc=x\r\nlast");
+
+    let err = Document::parse("a=\\u00", Dialect::Java).unwrap_err();
+    assert_eq!((err.line(), err.column()), (1, 6));
+}
+
+#[test]
+fn document_edit() {
+    let prop = "# Module\nid = test\nversionCode : 1\n\n# Description\ndescription=old";
+    let mut doc: Document = prop.parse().unwrap();
+
+    assert_eq!(doc.set("versionCode", "2").unwrap(), Some("1".to_owned()));
+    assert_eq!(doc.get("versionCode"), Some("2"));
+    assert_eq!(doc.set("author", "a b").unwrap(), None);
+    assert!(doc.insert_after("id", "name", " Test=Module").unwrap());
+    assert!(!doc.insert_after("missing", "name", "x").unwrap());
+    assert!(doc.rename("description", "my key").unwrap());
+    assert_eq!(doc.remove("id"), Some("test".to_owned()));
+    assert_eq!(doc.remove("id"), None);
+    assert_eq!(
+        doc.to_string(),
+        "# Module\nname = \\ Test=Module\nversionCode : 2\n\n# Description\nmy\\ key=old\nauthor=a b"
+    );
+
+    let doc: Document = doc.to_string().parse().unwrap();
+    assert_eq!(doc.get("name"), Some(" Test=Module"));
+    assert_eq!(doc.get("my key"), Some("old"));
+
+    let mut doc = Document::parse("a: 1\r\n", Dialect::Lenient).unwrap();
+    doc.set("b", "x").unwrap();
+    assert_eq!(doc.to_string(), "a: 1\r\nb: x\r\n");
+    assert!(doc.set("a", "1\nc: 2").is_err());
+    assert!(doc.set("a", "C:\\").is_err());
+    assert!(doc.set("c=d", "1").is_err());
+    assert!(doc.rename("a", "#a").is_err());
+    assert_eq!(doc.to_string(), "a: 1\r\nb: x\r\n");
+
+    let mut doc = Document::parse("empty\nbare", Dialect::Java).unwrap();
+    doc.set("empty", "val").unwrap();
+    doc.insert_after("bare", "new", "x").unwrap();
+    assert_eq!(doc.to_string(), "empty=val\nbare\nnew=x");
+}
+
+#[test]
+fn to_document_merge() {
+    #[derive(Serialize)]
+    struct Module {
+        id: &'static str,
+        version: &'static str,
+        #[serde(rename = "versionCode")]
+        version_code: i32,
+        author: &'static str,
+    }
+
+    let module = Module {
+        id: "test",
+        version: "v2.0",
+        version_code: 2,
+        author: "me",
+    };
+
+    let prop = "# Generated\nid=test\n\n# Bump on release\nversion = v1.0\nversionCode = 1\nupdateJson=x\n";
+    let mut doc: Document = prop.parse().unwrap();
+    serde_prop::to_document_merge(&mut doc, &module).unwrap();
+    assert_eq!(
+        doc.to_string(),
+        "# Generated\nid=test\n\n# Bump on release\nversion = v2.0\nversionCode = 2\nupdateJson=x\nauthor=me\n"
+    );
+
+    let mut doc: Document = prop.parse().unwrap();
+    Merge::new(&mut doc)
+        .with_anchor("id")
+        .with_remove_missing(true)
+        .merge(&module)
+        .unwrap();
+    assert_eq!(
+        doc.to_string(),
+        "# Generated\nid=test\nauthor=me\n\n# Bump on release\nversion = v2.0\nversionCode = 2\n"
+    );
+
+    let mut map = BTreeMap::new();
+    map.insert("id", "test\nversionCode=9");
+    let mut doc = Document::parse(prop, Dialect::Lenient).unwrap();
+    assert!(serde_prop::to_document_merge(&mut doc, &map).is_err());
+    assert_eq!(doc.to_string(), prop);
+
+    let prop = "a=1\nb=2\nc=3\n";
+    let mut doc = Document::parse(prop, Dialect::Lenient).unwrap();
+    let map = BTreeMap::from([("a", "new"), ("b", "x\ny")]);
+    assert!(serde_prop::to_document_merge(&mut doc, &map).is_err());
+    let map = BTreeMap::from([("a", "1"), ("c", "x\ny")]);
+    let merge = Merge::new(&mut doc).with_remove_missing(true);
+    assert!(merge.merge(&map).is_err());
+    assert_eq!(doc.to_string(), prop);
+}
+
+#[test]
+fn value() {
+    use serde_prop::{Map, Value};
+
+    let prop = "id=test\nversionCode=3\nname=Test\n";
+    let mut value: Value = serde_prop::from_str(prop).unwrap();
+    assert_eq!(value["id"], Value::from("test"));
+    assert_eq!(value["versionCode"].as_str(), Some("3"));
+    assert!(value["missing"].is_null());
+    assert_eq!(
+        value.as_map().unwrap().keys().collect::<Vec<_>>(),
+        ["id", "versionCode", "name"]
+    );
+
+    value["author"] = "me".into();
+    value["versionCode"] = 4.into();
+    assert_eq!(
+        serde_prop::to_string(&value).unwrap(),
+        "id=test\nversionCode=4\nname=Test\nauthor=me"
+    );
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    struct Module {
+        id: String,
+        #[serde(rename = "versionCode")]
+        version_code: i32,
+        enabled: Option<bool>,
+    }
+
+    let module: Module = serde_prop::from_value(value.clone()).unwrap();
+    assert_eq!(
+        module,
+        Module {
+            id: "test".to_owned(),
+            version_code: 4,
+            enabled: None,
+        }
+    );
+
+    let value = serde_prop::to_value(&module).unwrap();
+    let map: Map<String, Value> = [
+        ("id".to_owned(), "test".into()),
+        ("versionCode".to_owned(), 4.into()),
+        ("enabled".to_owned(), Value::Null),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(value, Value::Map(map));
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    enum Source {
+        Local(String),
+        Pair(u8, u8),
+        Remote { url: String },
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    struct Build {
+        hosts: Vec<String>,
+        version: (u8, u8),
+        huge: u128,
+        sources: Vec<Source>,
+    }
+
+    let build = Build {
+        hosts: vec!["a".to_owned(), "b".to_owned()],
+        version: (1, 2),
+        huge: u128::MAX,
+        sources: vec![
+            Source::Local("x".to_owned()),
+            Source::Pair(3, 4),
+            Source::Remote {
+                url: "y".to_owned(),
+            },
+        ],
+    };
+    let value = serde_prop::to_value(&build).unwrap();
+    assert_eq!(value["hosts"][1], Value::from("b"));
+    assert_eq!(value["hosts"].to_string(), "a,b");
+    assert_eq!(value["version"], Value::from(vec![1, 2]));
+    assert_eq!(
+        value["huge"].as_str(),
+        Some("340282366920938463463374607431768211455")
+    );
+    assert_eq!(value["sources"][1]["Pair"][0], Value::from(3));
+    assert_eq!(value["sources"][2]["Remote"]["url"], Value::from("y"));
+    assert!(value["hosts"][5].is_null());
+    assert_eq!(serde_prop::from_value::<Build>(value).unwrap(), build);
+
+    struct Bytes;
+
+    impl Serialize for Bytes {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(b"ab")
+        }
+    }
+
+    let value = serde_prop::to_value(Bytes).unwrap();
+    assert_eq!(value, Value::from(vec![0x61, 0x62]));
+    assert_eq!(serde_prop::from_value::<Vec<u8>>(value).unwrap(), b"ab");
+
+    let err =
+        serde_prop::from_value::<Module>(Value::from_iter([("versionCode", "x")])).unwrap_err();
+    assert_eq!(err.to_string(), "invalid value: string \"x\", expected i32");
+}
+
+#[test]
+fn duplicate_key() {
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Config<'a> {
+        host: &'a str,
+        port: u16,
+    }
+
+    let prop = "host=a\nport=80\n\nhost=b\n";
+    let err = serde_prop::from_str::<Config>(prop).unwrap_err();
+    assert_eq!(err.to_string(), "duplicate field `host`");
+
+    let parse = |duplicate_key| {
+        let mut de = Deserializer::from_str(prop).with_duplicate_key(duplicate_key);
+        Config::deserialize(&mut de)
+    };
+    assert_eq!(parse(DuplicateKey::First).unwrap().host, "a");
+    assert_eq!(parse(DuplicateKey::Last).unwrap().host, "b");
+    let err = parse(DuplicateKey::Error).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "host: duplicate key, first set at line 1 at line 4 column 1"
+    );
+    assert!(err.is_data());
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Collected {
+        host: Vec<String>,
+        port: Vec<u16>,
+        name: String,
+    }
+
+    let prop = "host=a\nport=80\nname=x\nhost=b\nport=y\nname=z\n";
+    let mut de = Deserializer::from_str(prop).with_duplicate_key(DuplicateKey::Collect);
+    let err = Collected::deserialize(&mut de).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "port: invalid value: string \"y\", expected unsigned integer at line 5 column 1"
+    );
+
+    let prop = "host=a\nport=80,443\nname=x\nhost=b\nport=8080\nname=z\n";
+    let mut de = Deserializer::from_str(prop).with_duplicate_key(DuplicateKey::Collect);
+    assert_eq!(
+        Collected::deserialize(&mut de).unwrap(),
+        Collected {
+            host: vec!["a".to_owned(), "b".to_owned()],
+            port: vec![80, 443, 8080],
+            name: "z".to_owned(),
+        }
+    );
+}
+
+#[test]
+fn dotted_keys() {
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    struct Server {
+        host: String,
+        port: u16,
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    struct Backend {
+        host: String,
+        weight: Option<u8>,
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    struct Config {
+        name: String,
+        server: Server,
+        backends: BTreeMap<String, Backend>,
+    }
+
+    let prop = "server.port=80\nname=test\nbackends.eu.host=eu.example.com\nserver.host=localhost\nbackends.us.host=us.example.com\nbackends.us.weight=2\n";
+    let mut de = Deserializer::from_str(prop).with_dotted_keys(true);
+    let config = Config::deserialize(&mut de).unwrap();
+    assert_eq!(config.server.port, 80);
+    assert_eq!(config.backends["eu"].weight, None);
+    assert_eq!(config.backends["us"].weight, Some(2));
+
+    let output = serde_prop::to_string(&config).unwrap();
+    assert_eq!(
+        output,
+        "name=test\nserver.host=localhost\nserver.port=80\nbackends.eu.host=eu.example.com\nbackends.eu.weight=\nbackends.us.host=us.example.com\nbackends.us.weight=2"
+    );
+    let mut de = Deserializer::from_str(&output).with_dotted_keys(true);
+    assert_eq!(Config::deserialize(&mut de).unwrap(), config);
+
+    let prop = "name=test\nserver.host=localhost\nserver.port=x\n";
+    let mut de = Deserializer::from_str(prop).with_dotted_keys(true);
+    let err = Config::deserialize(&mut de).unwrap_err();
+    assert_eq!(err.key(), Some("server.port"));
+    assert_eq!((err.line(), err.column()), (3, 1));
+
+    let mut map = BTreeMap::new();
+    map.insert("m", BTreeMap::from([("a.b", 1)]));
+    assert!(serde_prop::to_string(&map).is_err());
+    let map = BTreeMap::from([("ro.build.id", 1)]);
+    assert_eq!(serde_prop::to_string(&map).unwrap(), "ro.build.id=1");
+}
+
+#[test]
+fn seqs() {
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    struct Host {
+        name: String,
+        port: u16,
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    struct Build {
+        abi: Vec<String>,
+        version: (u8, u8, u8),
+        ports: [u16; 2],
+        flags: Vec<bool>,
+    }
+
+    let prop = "abi=arm64-v8a, armeabi-v7a\nversion=1,2,3\nports=80,443\nflags=\n";
+    let build: Build = serde_prop::from_str(prop).unwrap();
+    assert_eq!(build.abi, ["arm64-v8a", "armeabi-v7a"]);
+    assert_eq!(build.version, (1, 2, 3));
+    assert_eq!(build.ports, [80, 443]);
+    assert!(build.flags.is_empty());
+
+    let output = serde_prop::to_string(&build).unwrap();
+    assert_eq!(
+        output,
+        "abi=arm64-v8a,armeabi-v7a\nversion=1,2,3\nports=80,443\nflags="
+    );
+
+    let mut ser = Serializer::new(Vec::new()).with_seq_separator(';');
+    build.serialize(&mut ser).unwrap();
+    let output = String::from_utf8(ser.into_inner()).unwrap();
+    let mut de = Deserializer::from_str(&output).with_seq_separator(';');
+    assert_eq!(Build::deserialize(&mut de).unwrap(), build);
+
+    for v in [vec!["a,b", "c"], vec!["a", " x"], vec![""]] {
+        let map = BTreeMap::from([("v", v)]);
+        assert!(serde_prop::to_string(&map).is_err());
+        let mut ser = Serializer::new(Vec::new()).with_indexed_seqs(true);
+        map.serialize(&mut ser).unwrap();
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    struct Cluster {
+        hosts: Vec<String>,
+        backends: Vec<Host>,
+    }
+
+    let prop = "hosts[1]=b\nhosts.0=a\nbackends.0.name=eu\nbackends.0.port=80\nbackends[1].name=us\nbackends[1].port=81\n";
+    let mut de = Deserializer::from_str(prop).with_dotted_keys(true);
+    let cluster = Cluster::deserialize(&mut de).unwrap();
+    assert_eq!(cluster.hosts, ["a", "b"]);
+    assert_eq!(cluster.backends[1].name, "us");
+
+    let mut ser = Serializer::new(Vec::new()).with_indexed_seqs(true);
+    cluster.serialize(&mut ser).unwrap();
+    let output = String::from_utf8(ser.into_inner()).unwrap();
+    assert_eq!(
+        output,
+        "hosts.0=a\nhosts.1=b\nbackends.0.name=eu\nbackends.0.port=80\nbackends.1.name=us\nbackends.1.port=81"
+    );
+    let mut de = Deserializer::from_str(&output).with_dotted_keys(true);
+    assert_eq!(Cluster::deserialize(&mut de).unwrap(), cluster);
+
+    let prop = "hosts.0=a\nbackends.x.name=eu\nbackends.x.port=80\n";
+    let mut de = Deserializer::from_str(prop).with_dotted_keys(true);
+    let err = Cluster::deserialize(&mut de).unwrap_err();
+    assert_eq!(err.key(), Some("backends.x"));
+
+    assert!(serde_prop::to_string(&cluster).is_err());
+}
+
+#[test]
+fn to_string_unsupported() {
+    for err in [
+        serde_prop::to_string(&1).unwrap_err(),
+        serde_prop::to_string("name").unwrap_err(),
+        serde_prop::to_string(&vec![1, 2]).unwrap_err(),
+        serde_prop::to_string(&None::<BTreeMap<String, String>>).unwrap_err(),
+    ] {
+        assert_eq!(err.to_string(), "top-level must be a struct or map");
+        assert_eq!(err.classify(), Category::Data);
+    }
+
+    let mut map = BTreeMap::new();
+    map.insert("hosts", vec![BTreeMap::from([("name", "eu")])]);
+    let err = serde_prop::to_string(&map).unwrap_err();
+    assert_eq!(err.to_string(), "unsupported map in a delimited seq");
+}
+
+#[test]
+fn newtypes_and_enums() {
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    struct ModuleId(String);
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    struct Proxy {
+        host: String,
+        port: u16,
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    enum Mode {
+        Local,
+        Remote { url: String, retries: u8 },
+        Proxied(Proxy),
+        Timeout(u32),
+        Range(u16, u16),
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    struct Config {
+        id: ModuleId,
+        modes: BTreeMap<String, Mode>,
+    }
+
+    let prop = "\
+id=app
+modes.a=Local
+modes.b=Remote
+modes.b.url=https://example.com
+modes.b.retries=3
+modes.c=Proxied
+modes.c.host=localhost
+modes.c.port=8080
+modes.d=Timeout
+modes.d.0=30
+modes.e=Range
+modes.e.0=1
+modes.e.1=9
+";
+    let mut de = Deserializer::from_str(prop).with_dotted_keys(true);
+    let config = Config::deserialize(&mut de).unwrap();
+    assert_eq!(config.id, ModuleId("app".to_owned()));
+    assert_eq!(config.modes["a"], Mode::Local);
+    assert_eq!(
+        config.modes["b"],
+        Mode::Remote {
+            url: "https://example.com".to_owned(),
+            retries: 3
+        }
+    );
+    assert_eq!(config.modes["d"], Mode::Timeout(30));
+    assert_eq!(config.modes["e"], Mode::Range(1, 9));
+
+    let output = serde_prop::to_string(&config).unwrap();
+    assert_eq!(output, prop.trim_end());
+
+    let prop = "id=app\nmodes.b=Remote\nmodes.b.url=x\nmodes.b.retries=many\n";
+    let mut de = Deserializer::from_str(prop).with_dotted_keys(true);
+    let err = Config::deserialize(&mut de).unwrap_err();
+    assert_eq!(err.key(), Some("modes.b.retries"));
+
+    let mut de = Deserializer::from_str("id=app\nmodes.b=Remote\n").with_dotted_keys(true);
+    let err = Config::deserialize(&mut de).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "modes.b: invalid type: unit variant, expected struct variant at line 2 column 1"
+    );
+}
+
+#[test]
+fn chars_wide_ints_and_bytes() {
+    mod bytes {
+        use serde::{de, Deserializer, Serializer};
+        use std::fmt;
+
+        pub fn serialize<S: Serializer>(v: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(v)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Vec<u8>, D::Error> {
+            struct BytesVisitor;
+
+            impl de::Visitor<'_> for BytesVisitor {
+                type Value = Vec<u8>;
+
+                fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                    formatter.write_str("bytes")
+                }
+
+                fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+                    Ok(v)
+                }
+            }
+
+            deserializer.deserialize_byte_buf(BytesVisitor)
+        }
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    struct Record {
+        sep: char,
+        big: i128,
+        huge: u128,
+        #[serde(with = "bytes")]
+        key: Vec<u8>,
+    }
+
+    let record = Record {
+        sep: 'é',
+        big: i128::MIN,
+        huge: u128::MAX,
+        key: b"\x00\xffhi!".to_vec(),
+    };
+    let output = serde_prop::to_string(&record).unwrap();
+    assert_eq!(
+        output,
+        "sep=é\nbig=-170141183460469231731687303715884105728\n\
+         huge=340282366920938463463374607431768211455\nkey=00ff686921"
+    );
+    assert_eq!(serde_prop::from_str::<Record>(&output).unwrap(), record);
+
+    let formatter = CompactFormatter::new().with_bytes_encoding(BytesEncoding::Base64);
+    let mut ser = Serializer::with_formatter(Vec::new(), formatter);
+    record.serialize(&mut ser).unwrap();
+    let output = String::from_utf8(ser.into_inner()).unwrap();
+    assert!(output.ends_with("\nkey=AP9oaSE="));
+    let mut de = Deserializer::from_str(&output).with_bytes_encoding(BytesEncoding::Base64);
+    assert_eq!(Record::deserialize(&mut de).unwrap(), record);
+
+    let prop = "sep=ab\nbig=1\nhuge=1\nkey=\n";
+    let err = serde_prop::from_str::<Record>(prop).unwrap_err();
+    assert_eq!(err.key(), Some("sep"));
+    let prop = "sep=a\nbig=1\nhuge=1\nkey=0g\n";
+    let err = serde_prop::from_str::<Record>(prop).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "key: invalid value: string \"0g\", expected encoded bytes at line 4 column 1"
+    );
+}
+
+#[test]
+fn to_string_pretty() {
+    #[derive(Serialize)]
+    struct Server {
+        host: &'static str,
+        port: u16,
+    }
+
+    #[derive(Serialize)]
+    struct Config {
+        name: &'static str,
+        version: u8,
+        server: Server,
+        backends: BTreeMap<&'static str, &'static str>,
+    }
+
+    let config = Config {
+        name: "test",
+        version: 2,
+        server: Server {
+            host: "localhost",
+            port: 80,
+        },
+        backends: BTreeMap::from([("eu", "eu.example.com"), ("us", "us.example.com")]),
+    };
+
+    let output = serde_prop::to_string_pretty(&config).unwrap();
+    assert_eq!(
+        output,
+        "\
+name        = test
+version     = 2
+
+server.host = localhost
+server.port = 80
+
+backends.eu = eu.example.com
+backends.us = us.example.com
+"
+    );
+    let map: BTreeMap<String, String> = serde_prop::from_str(&output).unwrap();
+    assert_eq!(map["server.host"], "localhost");
+
+    let formatter = PrettyFormatter::new()
+        .with_separator(KeySeparator::Colon)
+        .with_alignment(false)
+        .with_crlf(true)
+        .with_group_spacing(false)
+        .with_trailing_newline(false);
+    let mut ser = Serializer::with_formatter(Vec::new(), formatter).with_line_width(12);
+    config.serialize(&mut ser).unwrap();
+    let output = String::from_utf8(ser.into_inner()).unwrap();
+    assert_eq!(
+        output,
+        "name: test\r\nversion: 2\r\nserver.host: localhost\r\nserver.port: 80\r\n\
+         backends.eu: eu.example.com\r\nbackends.us: us.example.com"
+    );
+
+    let mut ser = Serializer::with_formatter(Vec::new(), PrettyFormatter::new());
+    BTreeMap::<String, String>::new()
+        .serialize(&mut ser)
+        .unwrap();
+    assert!(ser.into_inner().is_empty());
+}
+
+#[test]
+fn to_string_comments() {
+    #[derive(Serialize)]
+    struct Server {
+        host: &'static str,
+        port: u16,
+    }
+
+    #[derive(Serialize)]
+    struct Module {
+        id: &'static str,
+        #[serde(rename = "my name")]
+        name: &'static str,
+        server: Server,
+    }
+
+    let module = Module {
+        id: "app",
+        name: "App",
+        server: Server {
+            host: "localhost",
+            port: 80,
+        },
+    };
+
+    let comments = BTreeMap::from([
+        ("my name", "Shown to users"),
+        ("server.port", "Two lines,\r\nand a\rthird\n"),
+        ("missing", "never written"),
+    ]);
+    let mut ser = Serializer::new(Vec::new())
+        .with_header("Generated by build.rs\n\nDo not edit")
+        .with_comments(comments.clone());
+    module.serialize(&mut ser).unwrap();
+    let output = String::from_utf8(ser.into_inner()).unwrap();
+    assert_eq!(
+        output,
+        "\
+# Generated by build.rs
+#
+# Do not edit
+
+id=app
+# Shown to users
+my\\ name=App
+server.host=localhost
+# Two lines,
+# and a
+# third
+server.port=80"
+    );
+    let map: BTreeMap<String, String> = serde_prop::from_str(&output).unwrap();
+    assert_eq!(map.len(), 4);
+    assert_eq!(map["my name"], "App");
+
+    let formatter = PrettyFormatter::new().with_crlf(true);
+    let mut ser = Serializer::with_formatter(Vec::new(), formatter)
+        .with_header("Generated")
+        .with_comments(comments)
+        .with_comment_marker(CommentMarker::Bang);
+    module.serialize(&mut ser).unwrap();
+    let output = String::from_utf8(ser.into_inner()).unwrap();
+    assert_eq!(
+        output,
+        "! Generated\r\n\r\n\
+         id          = app\r\n\
+         ! Shown to users\r\n\
+         my\\ name    = App\r\n\r\n\
+         server.host = localhost\r\n\
+         ! Two lines,\r\n! and a\r\n! third\r\n\
+         server.port = 80\r\n"
+    );
+    let map: BTreeMap<String, String> = serde_prop::from_str(&output).unwrap();
+    assert_eq!(map["my name"], "App");
+}
+
+#[test]
+fn to_string_key_order() {
+    use std::collections::HashMap;
+
+    #[derive(Serialize)]
+    struct Server {
+        port: u16,
+        host: &'static str,
+    }
+
+    #[derive(Serialize)]
+    struct Config {
+        version: u8,
+        server: Server,
+        env: HashMap<&'static str, &'static str>,
+        hosts: Vec<&'static str>,
+    }
+
+    let config = Config {
+        version: 1,
+        server: Server {
+            port: 80,
+            host: "localhost",
+        },
+        env: (0..12)
+            .map(|i| (["A", "B", "C", "D"][i % 4], "x"))
+            .collect(),
+        hosts: (0..11).map(|_| "h").collect(),
+    };
+
+    let mut ser = Serializer::new(Vec::new())
+        .with_indexed_seqs(true)
+        .with_sorted_keys(true);
+    config.serialize(&mut ser).unwrap();
+    let output = String::from_utf8(ser.into_inner()).unwrap();
+    let keys: Vec<&str> = output
+        .lines()
+        .map(|l| l.split('=').next().unwrap())
+        .collect();
+    assert_eq!(
+        keys,
+        [
+            "env.A",
+            "env.B",
+            "env.C",
+            "env.D",
+            "hosts.0",
+            "hosts.1",
+            "hosts.2",
+            "hosts.3",
+            "hosts.4",
+            "hosts.5",
+            "hosts.6",
+            "hosts.7",
+            "hosts.8",
+            "hosts.9",
+            "hosts.10",
+            "server.host",
+            "server.port",
+            "version",
+        ]
+    );
+
+    let mut ser = Serializer::new(Vec::new()).with_key_order(["server.host", "env", "server"]);
+    config.serialize(&mut ser).unwrap();
+    let output = String::from_utf8(ser.into_inner()).unwrap();
+    let keys: Vec<&str> = output
+        .lines()
+        .map(|l| l.split('=').next().unwrap())
+        .collect();
+    assert_eq!(keys[0], "server.host");
+    assert!(keys[1..5].iter().all(|k| k.starts_with("env.")));
+    assert_eq!(keys[5..], ["server.port", "version", "hosts"]);
+}
+
+#[test]
+fn option_policies() {
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    struct Module {
+        name: String,
+        author: Option<String>,
+        description: Option<String>,
+        #[serde(default)]
+        license: Option<String>,
+    }
+
+    let module = Module {
+        name: String::new(),
+        author: None,
+        description: Some(String::new()),
+        license: None,
+    };
+
+    let de = serde_prop::from_str::<Module>("name=\nauthor=\ndescription=\n").unwrap();
+    assert_eq!((de.name.as_str(), de.description), ("", None));
+    let mut de = Deserializer::from_str("name=\nauthor=\ndescription=\n").with_empty_strings(true);
+    let de = Module::deserialize(&mut de).unwrap();
+    assert_eq!(
+        (de.name.as_str(), de.description),
+        ("", Some(String::new()))
+    );
+
+    let mut ser = Serializer::new(Vec::new()).with_null_token("null");
+    module.serialize(&mut ser).unwrap();
+    let output = String::from_utf8(ser.into_inner()).unwrap();
+    assert_eq!(output, "name=\nauthor=null\ndescription=\nlicense=null");
+    let mut de = Deserializer::from_str(&output).with_null_token("null");
+    assert_eq!(Module::deserialize(&mut de).unwrap(), module);
+
+    let unit = BTreeMap::from([("a", ())]);
+    let mut ser = Serializer::new(Vec::new()).with_null_token("null");
+    unit.serialize(&mut ser).unwrap();
+    let output = String::from_utf8(ser.into_inner()).unwrap();
+    assert_eq!(output, "a=null");
+    let mut de = Deserializer::from_str(&output).with_null_token("null");
+    assert_eq!(BTreeMap::<&str, ()>::deserialize(&mut de).unwrap(), unit);
+
+    let mut ser = Serializer::new(Vec::new())
+        .with_skip_none(true)
+        .with_null_token("null");
+    module.serialize(&mut ser).unwrap();
+    let output = String::from_utf8(ser.into_inner()).unwrap();
+    assert_eq!(output, "name=\ndescription=");
+    let mut de = Deserializer::from_str(&output).with_empty_strings(true);
+    assert_eq!(Module::deserialize(&mut de).unwrap(), module);
+
+    let mut de = Deserializer::from_str("name=x\nauthor=null\ndescription=\n")
+        .with_null_token("null")
+        .with_type_inference(true);
+    let map: BTreeMap<String, serde_prop::Value> = Deserialize::deserialize(&mut de).unwrap();
+    assert!(map["author"].is_null());
+    assert_eq!(map["description"].as_str(), Some(""));
+}
+
+#[test]
+fn lenient_literals() {
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    struct Props {
+        flags: Vec<bool>,
+        mask: u32,
+        mode: u16,
+        bits: u8,
+        size: i64,
+        offset: i32,
+        huge: u128,
+    }
+
+    let prop = "\
+flags=1,0,yes,NO,On,off,TRUE
+mask=0x1F
+mode=0o755
+bits=0b1010
+size=1_000
+offset=-0x10
+huge=0xffff_ffff_ffff_ffff_ffff
+";
+    assert!(serde_prop::from_str::<Props>(prop).is_err());
+
+    let mut de = Deserializer::from_str(prop).with_lenient_literals(true);
+    let props = Props::deserialize(&mut de).unwrap();
+    assert_eq!(
+        props,
+        Props {
+            flags: vec![true, false, true, false, true, false, true],
+            mask: 31,
+            mode: 0o755,
+            bits: 10,
+            size: 1000,
+            offset: -16,
+            huge: 0xffff_ffff_ffff_ffff_ffff,
+        }
+    );
+
+    let mut de = Deserializer::from_str("flag=maybe").with_lenient_literals(true);
+    assert!(BTreeMap::<String, bool>::deserialize(&mut de).is_err());
+    for prop in ["n=0x", "n=_1", "n=1_", "n=-1", "n=0x100", "n=+-1"] {
+        let mut de = Deserializer::from_str(prop).with_lenient_literals(true);
+        let result = BTreeMap::<String, u8>::deserialize(&mut de);
+        assert!(result.is_err(), "{}", prop);
+    }
+
+    let formatter = CompactFormatter::new()
+        .with_hex_integers(true)
+        .with_numeric_bools(true);
+    let mut ser = Serializer::with_formatter(Vec::new(), formatter);
+    props.serialize(&mut ser).unwrap();
+    let output = String::from_utf8(ser.into_inner()).unwrap();
+    assert_eq!(
+        output,
+        "flags=1,0,1,0,1,0,1\nmask=0x1f\nmode=0x1ed\nbits=0xa\nsize=0x3e8\n\
+         offset=-0x10\nhuge=0xffffffffffffffffffff"
+    );
+    let mut de = Deserializer::from_str(&output).with_lenient_literals(true);
+    assert_eq!(Props::deserialize(&mut de).unwrap(), props);
+
+    let formatter = CompactFormatter::new()
+        .with_hex_integers(true)
+        .with_numeric_bools(true);
+    let mut ser = Serializer::with_formatter(Vec::new(), formatter);
+    let map = BTreeMap::from([(10_u32, BTreeMap::from([(true, 1)]))]);
+    map.serialize(&mut ser).unwrap();
+    let output = String::from_utf8(ser.into_inner()).unwrap();
+    assert_eq!(output, "10.true=0x1");
+}